@@ -8,7 +8,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 fn build_protos() -> std::io::Result<()> {
-    tonic_build::configure().build_server(false).compile(
+    // The server stubs are only used by the mock Agora server of the test utilities.
+    let build_server = std::env::var_os("CARGO_FEATURE_TEST_UTILS").is_some();
+
+    tonic_build::configure().build_server(build_server).compile(
         &[
             "proto/account/v4/account_service.proto",
             "proto/airdrop/v4/airdrop_service.proto",
@@ -0,0 +1,308 @@
+use {
+    crate::{
+        client::{
+            client::{
+                endpoint, Client, DEFAULT_BALANCE_CONCURRENCY, DEFAULT_CONFIRMATION_POLL_INTERVAL,
+                DEFAULT_MAX_NONCE_ATTEMPTS,
+            },
+            environment::Environment,
+            internal::{
+                cache::CacheConfig,
+                metadata::{MetadataHook, MetadataInterceptor},
+                InternalClient,
+            },
+        },
+        error::Error,
+        retry::{strategy::Strategy, Retrier},
+        solana::commitment::Commitment,
+    },
+    std::{sync::Arc, time::Duration},
+    tonic::{
+        metadata::MetadataMap,
+        transport::{Certificate, ClientTlsConfig, Endpoint, Identity},
+    },
+};
+
+/// Configures and builds a Client.
+///
+/// By default, the client connects to the Agora endpoint of the environment using TLS.
+pub struct ClientBuilder {
+    env: Environment,
+    endpoint: Option<String>,
+    app_index: u16,
+    commitment: Commitment,
+    retrier: Retrier,
+    max_nonce_attempts: u32,
+    balance_concurrency: usize,
+    confirmation_poll_interval: Duration,
+    ca_certificate: Option<Certificate>,
+    identity: Option<Identity>,
+    connect_timeout: Option<Duration>,
+    timeout: Option<Duration>,
+    tcp_keepalive: Option<Duration>,
+    http2_keep_alive_interval: Option<Duration>,
+    keep_alive_timeout: Option<Duration>,
+    keep_alive_while_idle: bool,
+    headers: Vec<(String, String)>,
+    metadata_hook: Option<MetadataHook>,
+    cache_config: CacheConfig,
+}
+
+impl ClientBuilder {
+    pub fn new(env: Environment) -> ClientBuilder {
+        ClientBuilder {
+            env,
+            endpoint: None,
+            app_index: 0,
+            commitment: Commitment::default(),
+            retrier: Retrier::default(),
+            max_nonce_attempts: DEFAULT_MAX_NONCE_ATTEMPTS,
+            balance_concurrency: DEFAULT_BALANCE_CONCURRENCY,
+            confirmation_poll_interval: DEFAULT_CONFIRMATION_POLL_INTERVAL,
+            ca_certificate: None,
+            identity: None,
+            connect_timeout: None,
+            timeout: None,
+            tcp_keepalive: None,
+            http2_keep_alive_interval: None,
+            keep_alive_timeout: None,
+            keep_alive_while_idle: false,
+            headers: Vec::new(),
+            metadata_hook: None,
+            cache_config: CacheConfig::default(),
+        }
+    }
+
+    /// Sets the URL of the Agora endpoint, overriding the endpoint of the environment.
+    ///
+    /// TLS is only used if the URL has an `https` scheme, which allows connecting to a local
+    /// endpoint such as `http://localhost:8085` in plaintext.
+    pub fn endpoint(mut self, url: &str) -> ClientBuilder {
+        self.endpoint = Some(url.to_string());
+        self
+    }
+
+    pub fn app_index(mut self, app_index: u16) -> ClientBuilder {
+        self.app_index = app_index;
+        self
+    }
+
+    /// Sets the commitment used by the client when none is provided to a method.
+    pub fn commitment(mut self, commitment: Commitment) -> ClientBuilder {
+        self.commitment = commitment;
+        self
+    }
+
+    /// Sets the retry strategies used for calls to Agora.
+    pub fn retry_strategies(mut self, strategies: Vec<Box<dyn Strategy>>) -> ClientBuilder {
+        self.retrier = Retrier::new(strategies);
+        self
+    }
+
+    /// Sets the maximum number of times a transaction will be submitted if it fails due to a bad
+    /// nonce.
+    pub fn max_nonce_attempts(mut self, max_nonce_attempts: u32) -> ClientBuilder {
+        self.max_nonce_attempts = max_nonce_attempts;
+        self
+    }
+
+    /// Sets the maximum number of balances retrieved concurrently by `Client::get_balances`.
+    pub fn balance_concurrency(mut self, balance_concurrency: usize) -> ClientBuilder {
+        self.balance_concurrency = balance_concurrency;
+        self
+    }
+
+    /// Sets the interval at which `Client::confirm_transaction` checks the state of a transaction.
+    pub fn confirmation_poll_interval(mut self, interval: Duration) -> ClientBuilder {
+        self.confirmation_poll_interval = interval;
+        self
+    }
+
+    /// Sets a PEM-encoded CA certificate used to verify the server, instead of the system roots.
+    pub fn ca_certificate(mut self, pem: &[u8]) -> ClientBuilder {
+        self.ca_certificate = Some(Certificate::from_pem(pem));
+        self
+    }
+
+    /// Sets a PEM-encoded certificate and private key used to authenticate the client.
+    pub fn identity(mut self, cert_pem: &[u8], key_pem: &[u8]) -> ClientBuilder {
+        self.identity = Some(Identity::from_pem(cert_pem, key_pem));
+        self
+    }
+
+    /// Sets the timeout for connecting to Agora.
+    pub fn connect_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the timeout for each request to Agora.
+    pub fn timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the interval of TCP keepalive probes.
+    pub fn tcp_keepalive(mut self, interval: Duration) -> ClientBuilder {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Sets the interval of HTTP2 keep-alive pings.
+    pub fn http2_keep_alive_interval(mut self, interval: Duration) -> ClientBuilder {
+        self.http2_keep_alive_interval = Some(interval);
+        self
+    }
+
+    /// Sets how long to wait for a keep-alive ping to be acknowledged before closing the connection.
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.keep_alive_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets whether or not keep-alive pings are sent while there are no open requests.
+    pub fn keep_alive_while_idle(mut self, enabled: bool) -> ClientBuilder {
+        self.keep_alive_while_idle = enabled;
+        self
+    }
+
+    /// Adds a header to every request to Agora, in addition to the headers added by the SDK.
+    pub fn header(mut self, key: &str, value: &str) -> ClientBuilder {
+        self.headers.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sets a function that is called with the metadata of every request to Agora, after the SDK
+    /// headers have been added.
+    pub fn metadata_hook<F>(mut self, hook: F) -> ClientBuilder
+    where
+        F: Fn(&mut MetadataMap) + Send + Sync + 'static,
+    {
+        self.metadata_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Sets how long the service config is cached for. Defaults to 30 minutes.
+    ///
+    /// The cached config can be refreshed at any time with `Client::refresh_service_config`.
+    pub fn service_config_ttl(mut self, ttl: Duration) -> ClientBuilder {
+        self.cache_config.service_config_ttl = ttl;
+        self
+    }
+
+    /// Sets how long a recent blockhash is cached for. Defaults to 1 second.
+    ///
+    /// Transactions with identical instructions that are signed with the same blockhash will have the
+    /// same signature, so only one of them can be submitted.
+    pub fn blockhash_ttl(mut self, ttl: Duration) -> ClientBuilder {
+        self.cache_config.blockhash_ttl = ttl;
+        self
+    }
+
+    /// Returns the client.
+    ///
+    /// The connection to Agora is established on first use, and is automatically re-established if
+    /// it is lost.
+    ///
+    /// Must be called from within a Tokio runtime.
+    pub fn build(self) -> Result<Client, Error> {
+        let channel = self.get_endpoint()?.connect_lazy();
+        let interceptor =
+            MetadataInterceptor::new(self.app_index, &self.headers, self.metadata_hook)?;
+
+        Ok(Client {
+            internal: InternalClient::new(channel, interceptor, self.retrier, self.cache_config),
+            app_index: self.app_index,
+            env: self.env,
+            commitment: self.commitment,
+            max_nonce_attempts: self.max_nonce_attempts,
+            balance_concurrency: self.balance_concurrency,
+            confirmation_poll_interval: self.confirmation_poll_interval,
+        })
+    }
+
+    fn get_endpoint(&self) -> Result<Endpoint, Error> {
+        let url = match &self.endpoint {
+            Some(url) => url.clone(),
+            None => match self.env {
+                Environment::Production => endpoint::PRODUCTION.to_string(),
+                Environment::Test => endpoint::TEST.to_string(),
+            },
+        };
+
+        let mut endpoint =
+            Endpoint::from_shared(url.clone()).map_err(|_| Error::InvalidEndpoint(url))?;
+
+        if endpoint.uri().scheme_str() == Some("https") {
+            let mut tls = ClientTlsConfig::new();
+            if let Some(certificate) = &self.ca_certificate {
+                tls = tls.ca_certificate(certificate.clone());
+            }
+            if let Some(identity) = &self.identity {
+                tls = tls.identity(identity.clone());
+            }
+
+            endpoint = endpoint.tls_config(tls)?;
+        }
+
+        if let Some(timeout) = self.connect_timeout {
+            endpoint = endpoint.connect_timeout(timeout);
+        }
+        if let Some(timeout) = self.timeout {
+            endpoint = endpoint.timeout(timeout);
+        }
+        if let Some(interval) = self.http2_keep_alive_interval {
+            endpoint = endpoint.http2_keep_alive_interval(interval);
+        }
+        if let Some(timeout) = self.keep_alive_timeout {
+            endpoint = endpoint.keep_alive_timeout(timeout);
+        }
+
+        Ok(endpoint
+            .tcp_keepalive(self.tcp_keepalive)
+            .keep_alive_while_idle(self.keep_alive_while_idle))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn endpoint_defaults_to_environment() {
+        let endpoint = ClientBuilder::new(Environment::Test)
+            .get_endpoint()
+            .unwrap();
+        assert_eq!(
+            endpoint.uri().to_string(),
+            "https://api.agorainfra.dev:443/"
+        );
+
+        let endpoint = ClientBuilder::new(Environment::Production)
+            .get_endpoint()
+            .unwrap();
+        assert_eq!(
+            endpoint.uri().to_string(),
+            "https://api.agorainfra.net:443/"
+        );
+    }
+
+    #[test]
+    fn custom_endpoint() {
+        let endpoint = ClientBuilder::new(Environment::Test)
+            .endpoint("http://localhost:8085")
+            .get_endpoint()
+            .unwrap();
+
+        assert_eq!(endpoint.uri().to_string(), "http://localhost:8085/");
+    }
+
+    #[test]
+    fn invalid_endpoint() {
+        let result = ClientBuilder::new(Environment::Test)
+            .endpoint("not a url")
+            .get_endpoint();
+
+        assert!(matches!(result, Err(Error::InvalidEndpoint(_))));
+    }
+}
@@ -2,24 +2,30 @@ use {
     crate::{
         client::{
             account_resolution::AccountResolution,
+            builder::ClientBuilder,
+            earn_batch::submit_earn_batch,
             environment::Environment,
-            internal::{
-                account::InternalAccountClient,
-                airdrop::InternalAirdropClient,
-                transaction::{InternalTransactionClient, SubmitTransactionResult},
-                InternalClient,
-            },
-            {get_signers_and_funder, partial_sign},
+            events::{watch_account, EventStream},
+            history::{get_history, history_stream, Direction, HistoryPage, HistoryStream},
+            internal::{transaction::SubmitTransactionResult, InternalClient},
+            {get_mint_from_config, get_signers_and_funder, partial_sign},
         },
         error::{Error, TransactionError},
-        gen::kin::agora::account::v4 as account_pb,
         gen::kin::agora::common::v3 as model_pb_v3,
         key::{private::PrivateKey, public::PublicKey},
-        model::{payment::Payment, transaction::TransactionData},
+        model::{
+            account::{AccountInfo, AggregateBalance},
+            earn::{EarnBatch, EarnBatchResult},
+            payment::{Payment, PaymentReceipt},
+            transaction::{TransactionData, TransactionState},
+            transaction_id::TransactionId,
+        },
+        retry::{strategy::Strategy, Retrier},
         solana::{
             commitment::Commitment, token::instruction::create_assoc_account_and_set_close_auth,
         },
     },
+    futures::stream::{self, StreamExt},
     solana_sdk::{
         instruction::Instruction,
         pubkey::Pubkey as SolanaPublicKey,
@@ -27,7 +33,7 @@ use {
         transaction::Transaction as SolanaTransaction,
     },
     spl_associated_token_account::get_associated_token_address,
-    std::convert::TryInto,
+    std::{convert::TryInto, time::Duration},
 };
 
 type Result<T> = std::result::Result<T, Error>;
@@ -37,104 +43,149 @@ pub mod endpoint {
     pub const TEST: &str = "https://api.agorainfra.dev:443";
 }
 
+/// The default maximum number of times a transaction will be submitted if it fails due to a bad nonce.
+pub const DEFAULT_MAX_NONCE_ATTEMPTS: u32 = 3;
+
+/// The default maximum number of balances retrieved concurrently by `get_balances`.
+pub const DEFAULT_BALANCE_CONCURRENCY: usize = 10;
+
+/// The default interval at which `confirm_transaction` checks the state of a transaction.
+pub const DEFAULT_CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
 /// An interface for accessing Agora features.
+#[derive(Clone)]
 pub struct Client {
     pub internal: InternalClient,
     pub app_index: u16,
     pub env: Environment,
+
+    /// The commitment used when none is provided to a method.
+    pub commitment: Commitment,
+
+    /// The maximum number of times a transaction will be signed with a fresh blockhash and
+    /// submitted if it fails due to a bad nonce.
+    pub max_nonce_attempts: u32,
+
+    /// The maximum number of balances retrieved concurrently by `get_balances`.
+    pub balance_concurrency: usize,
+
+    /// The interval at which `confirm_transaction` checks the state of a transaction.
+    pub confirmation_poll_interval: Duration,
 }
 
 impl Client {
-    pub async fn new(env: Environment, app_index: Option<u16>) -> Client {
-        let app_index = app_index.unwrap_or_default();
+    /// Returns a client connected to the Agora endpoint of the environment.
+    ///
+    /// Use `ClientBuilder` to connect to a custom endpoint, or to configure the connection.
+    pub async fn new(env: Environment, app_index: Option<u16>) -> Result<Client> {
+        ClientBuilder::new(env)
+            .app_index(app_index.unwrap_or_default())
+            .build()
+    }
 
-        let endpoint = match env {
-            Environment::Production => endpoint::PRODUCTION,
-            Environment::Test => endpoint::TEST,
-        };
+    /// Sets the retry strategies used for calls to Agora.
+    ///
+    /// By default, calls that fail with a transient error are attempted up to 5 times, with a
+    /// binary exponential backoff.
+    pub fn set_retry_strategies(&mut self, strategies: Vec<Box<dyn Strategy>>) {
+        self.internal.set_retrier(Retrier::new(strategies));
+    }
 
-        let account = InternalAccountClient::new(endpoint).await.unwrap();
-        let tx = InternalTransactionClient::new(endpoint).await.unwrap();
-        let airdrop = InternalAirdropClient::new(endpoint).await.unwrap();
+    /// Retrieves the service config from Agora, replacing the cached config.
+    pub async fn refresh_service_config(&self) -> Result<()> {
+        self.internal.tx.refresh_service_config().await?;
 
-        Client {
-            internal: InternalClient {
-                account,
-                tx,
-                airdrop,
-            },
-            app_index,
-            env,
-        }
+        Ok(())
     }
 
     /// Creates a new Kin account.
     pub async fn create_account(
-        &mut self,
+        &self,
         key: &PrivateKey,
         commitment: Option<Commitment>,
         subsidizer: Option<&PrivateKey>,
     ) -> Result<()> {
-        let config = self.internal.tx.get_service_config().await;
-        let hash = self.internal.tx.get_recent_blockhash().await;
+        let config = self.internal.tx.get_service_config().await?;
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let hash = self.internal.tx.get_recent_blockhash().await?;
+
+            let result = self
+                .internal
+                .account
+                .create_account(
+                    key,
+                    commitment.unwrap_or(self.commitment),
+                    self.app_index,
+                    subsidizer,
+                    &config,
+                    &hash,
+                )
+                .await;
 
-        self.internal
-            .account
-            .create_account(
-                key,
-                commitment.unwrap_or_default(),
-                self.app_index,
-                subsidizer,
-                &config,
-                &hash,
-            )
-            .await?;
+            match result {
+                Err(Error::TransactionFailed(TransactionError::BadNonce(_))) => {
+                    self.internal.tx.invalidate_blockhash();
 
-        Ok(())
+                    if attempts < self.max_nonce_attempts {
+                        continue;
+                    }
+
+                    return result;
+                }
+                _ => return result,
+            }
+        }
     }
 
     /// Resolves the token accounts owned by the specified account on kin 4.
-    pub async fn resolve_token_accounts(&mut self, account: &PublicKey) -> Vec<PublicKey> {
+    pub async fn resolve_token_accounts(&self, account: &PublicKey) -> Result<Vec<PublicKey>> {
         let account_infos = self
             .internal
             .account
             .resolve_token_accounts(account, false)
-            .await;
+            .await?;
 
-        let mut accounts = Vec::new();
-        for info in account_infos {
-            if let Some(id) = info.account_id {
-                accounts.push(PublicKey::new(&id.value));
-            }
-        }
+        Ok(account_infos.iter().map(|info| info.account_id).collect())
+    }
 
-        accounts
+    /// Resolves the token accounts owned by the specified account on kin 4, along with their info.
+    pub async fn resolve_token_account_infos(
+        &self,
+        account: &PublicKey,
+    ) -> Result<Vec<AccountInfo>> {
+        self.internal
+            .account
+            .resolve_token_accounts(account, true)
+            .await
     }
 
     /// Merges all of an account's token accounts into one.
     pub async fn merge_token_accounts(
-        &mut self,
+        &self,
         key: &PrivateKey,
         create_associated_account: bool,
         commitment: Option<Commitment>,
         subsidizer: Option<&PrivateKey>,
-    ) -> Result<Option<Vec<u8>>> {
+    ) -> Result<Option<TransactionId>> {
         let accounts = self
             .internal
             .account
             .resolve_token_accounts(&key.public_key(), true)
-            .await;
+            .await?;
 
         if !Self::enough_accounts_for_merge(accounts.len(), create_associated_account) {
             return Ok(None);
         }
 
-        let config = self.internal.tx.get_service_config().await;
+        let config = self.internal.tx.get_service_config().await?;
         let (signers, funder) = get_signers_and_funder(key, subsidizer, &config)?;
 
-        let mut dest = SolanaPublicKey::new(&accounts[0].account_id.as_ref().unwrap().value);
+        let mut dest = accounts[0].account_id.to_solana_key();
         let owner = key.public_key().to_solana_key();
-        let mint = SolanaPublicKey::new(&config.token.as_ref().unwrap().value);
+        let mint = get_mint_from_config(&config)?;
 
         let mut instructions = Vec::new();
         if create_associated_account {
@@ -153,7 +204,7 @@ impl Client {
 
         instructions.append(&mut Self::get_merge_instructions(
             &accounts, &dest, &owner, &funder,
-        ));
+        )?);
 
         let tx = &mut SolanaTransaction::new_with_payer(&instructions, Some(&funder));
 
@@ -164,9 +215,21 @@ impl Client {
         Ok(result.tx_id)
     }
 
+    /// Retrieves the info of a token account.
+    pub async fn get_account_info(
+        &self,
+        account: &PublicKey,
+        commitment: Option<Commitment>,
+    ) -> Result<AccountInfo> {
+        self.internal
+            .account
+            .get_account_info(account, commitment.unwrap_or(self.commitment))
+            .await
+    }
+
     /// Retrieves the balance for an account.
     pub async fn get_balance(
-        &mut self,
+        &self,
         account: &PublicKey,
         commitment: Option<Commitment>,
         account_resolution: Option<AccountResolution>,
@@ -174,39 +237,153 @@ impl Client {
         let account_info = self
             .internal
             .account
-            .get_account_info(account, commitment.unwrap_or_default())
+            .get_account_info(account, commitment.unwrap_or(self.commitment))
             .await;
         let resolution = account_resolution.unwrap_or_default();
 
         match account_info {
             Ok(info) => Ok(info.balance),
-            Err(err) => match err {
-                TransactionError::AccountDoesNotExist(_) => {
-                    if resolution == AccountResolution::Preferred {
-                        let account_infos = self
-                            .internal
-                            .account
-                            .resolve_token_accounts(account, true)
-                            .await;
-                        if !account_infos.is_empty() {
-                            return Ok(account_infos[0].balance);
-                        }
+            Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(id))) => {
+                if resolution == AccountResolution::Preferred {
+                    let account_infos = self
+                        .internal
+                        .account
+                        .resolve_token_accounts(account, true)
+                        .await?;
+                    if !account_infos.is_empty() {
+                        return Ok(account_infos[0].balance);
                     }
-
-                    Err(err.into())
                 }
-                _ => Err(err.into()),
-            },
+
+                Err(TransactionError::AccountDoesNotExist(id).into())
+            }
+            Err(err) => Err(err),
         }
     }
 
+    /// Retrieves the balances of multiple accounts, in the same order as the accounts.
+    ///
+    /// Up to `balance_concurrency` balances are retrieved at a time. Each balance is resolved as in
+    /// `get_balance`, and a failure to retrieve one does not affect the others.
+    pub async fn get_balances(
+        &self,
+        accounts: &[PublicKey],
+        commitment: Option<Commitment>,
+        account_resolution: Option<AccountResolution>,
+    ) -> Vec<Result<i64>> {
+        stream::iter(accounts)
+            .map(|account| self.get_balance(account, commitment, account_resolution))
+            .buffered(self.balance_concurrency.max(1))
+            .collect()
+            .await
+    }
+
+    /// Retrieves a page of the transaction history of an account.
+    ///
+    /// Without a cursor, the first page is returned. Use the `next_cursor` of a page to retrieve the
+    /// page that follows it; an empty page marks the end of the history.
+    pub async fn get_history(
+        &self,
+        account: &PublicKey,
+        cursor: Option<&[u8]>,
+        direction: Option<Direction>,
+    ) -> Result<HistoryPage> {
+        get_history(
+            &self.internal.tx,
+            account,
+            cursor,
+            direction.unwrap_or_default(),
+        )
+        .await
+    }
+
+    /// Returns a stream of the whole transaction history of an account, retrieving pages as needed.
+    pub fn history(&self, account: &PublicKey, direction: Option<Direction>) -> HistoryStream {
+        history_stream(
+            self.internal.tx.clone(),
+            account,
+            direction.unwrap_or_default(),
+        )
+    }
+
+    /// Returns a stream of the events of an account, such as balance updates and transactions.
+    ///
+    /// If the connection to Agora is lost, the stream reconnects and resumes without emitting the
+    /// same transaction twice. The stream ends after an error that cannot be recovered from.
+    pub fn watch_account(&self, account: &PublicKey) -> EventStream {
+        watch_account(self.internal.account.clone(), account)
+    }
+
+    /// Retrieves the combined balance of all of the token accounts owned by an account, along with the
+    /// balance of each of them.
+    ///
+    /// If the account does not own any token accounts but is a token account itself, its balance alone
+    /// is returned.
+    pub async fn get_aggregate_balance(&self, owner: &PublicKey) -> Result<AggregateBalance> {
+        let accounts = self
+            .internal
+            .account
+            .resolve_token_accounts(owner, true)
+            .await?;
+        if !accounts.is_empty() {
+            return Ok(AggregateBalance::new(accounts));
+        }
+
+        let info = self
+            .internal
+            .account
+            .get_account_info(owner, self.commitment)
+            .await?;
+
+        Ok(AggregateBalance::new(vec![info]))
+    }
+
     /// Retrieves the TransactionData for a transaction id.
     pub async fn get_transaction(
-        &mut self,
-        tx_id: &[u8],
+        &self,
+        tx_id: &TransactionId,
         commitment: Option<Commitment>,
-    ) -> TransactionData {
-        self.internal.tx.get_transaction(tx_id, commitment).await
+    ) -> Result<TransactionData> {
+        let commitment = commitment.unwrap_or(self.commitment);
+
+        self.internal
+            .tx
+            .get_transaction(tx_id, Some(commitment))
+            .await
+    }
+
+    /// Waits until a transaction has succeeded or failed at a commitment, and returns its data.
+    ///
+    /// The transaction is retrieved every `confirmation_poll_interval` until its state is final. If
+    /// it is not final within the timeout, `Error::ConfirmationTimeout` is returned.
+    pub async fn confirm_transaction(
+        &self,
+        tx_id: &TransactionId,
+        commitment: Option<Commitment>,
+        timeout: Duration,
+    ) -> Result<TransactionData> {
+        let commitment = commitment.unwrap_or(self.commitment);
+
+        let confirm = async {
+            loop {
+                let tx = self
+                    .internal
+                    .tx
+                    .get_transaction(tx_id, Some(commitment))
+                    .await?;
+
+                match tx.tx_state {
+                    TransactionState::Success | TransactionState::Failed => return Ok(tx),
+                    TransactionState::Unknown | TransactionState::Pending => {
+                        tokio::time::sleep(self.confirmation_poll_interval).await
+                    }
+                }
+            }
+        };
+
+        tokio::time::timeout(timeout, confirm)
+            .await
+            .map_err(|_| Error::ConfirmationTimeout)?
     }
 
     /// Submits a payment.
@@ -214,13 +391,13 @@ impl Client {
     /// If the payment has an invoice, an app index _must_ be set.
     /// If the payment has a memo, an invoice cannot also be provided.
     pub async fn submit_payment(
-        &mut self,
+        &self,
         mut payment: Payment,
         commitment: Option<Commitment>,
         sender_resolution: Option<AccountResolution>,
         destination_resolution: Option<AccountResolution>,
         sender_create: Option<bool>,
-    ) -> Result<Option<Vec<u8>>> {
+    ) -> Result<PaymentReceipt> {
         use crate::client::payment_submission::submit_payment;
 
         submit_payment(
@@ -234,71 +411,110 @@ impl Client {
         .await
     }
 
+    /// Submits a batch of earns, in as few transactions as they fit in.
+    ///
+    /// If the earns have invoices, an app index _must_ be set. The transactions are submitted in
+    /// order, and submission stops at the first transaction that fails.
+    pub async fn submit_earn_batch(
+        &self,
+        batch: EarnBatch,
+        commitment: Option<Commitment>,
+        sender_resolution: Option<AccountResolution>,
+    ) -> Result<EarnBatchResult> {
+        submit_earn_batch(self, &batch, commitment, sender_resolution).await
+    }
+
     /// Requests an airdrop of Kin to a Kin account.
     /// Only available on Kin 4 on the test environment.
+    ///
+    /// Airdrops are not idempotent, so failed requests are never retried.
     pub async fn request_airdrop(
-        &mut self,
+        &self,
         public_key: &PublicKey,
         quarks: u64,
         commitment: Option<Commitment>,
-    ) -> Result<Vec<u8>> {
+    ) -> Result<TransactionId> {
         self.internal
             .airdrop
-            .request_airdrop(public_key, quarks, commitment.unwrap_or_default())
+            .request_airdrop(public_key, quarks, commitment.unwrap_or(self.commitment))
             .await
     }
 
+    /// Signs and submits a transaction.
+    ///
+    /// If the transaction fails due to a bad nonce, it is signed again with a fresh blockhash and
+    /// resubmitted, up to `max_nonce_attempts` times in total.
     pub async fn sign_and_submit_tx(
-        &mut self,
+        &self,
         signers: &[&PrivateKey],
         tx: &mut SolanaTransaction,
         commitment: Option<Commitment>,
         invoice_list: Option<&model_pb_v3::InvoiceList>,
         dedupe_id: Option<&Vec<u8>>,
     ) -> Result<SubmitTransactionResult> {
-        let hash = self.internal.tx.get_recent_blockhash().await;
-        partial_sign(tx, signers, &hash);
-
-        let mut remote_signed = false;
-        if Self::needs_subsidizer_signature(tx) {
-            let result = self.internal.tx.sign_transaction(tx, invoice_list).await;
-
-            // Error
-            if result.invoice_errors.is_some() {
-                return Ok(SubmitTransactionResult {
-                    tx_id: None,
-                    invoice_errors: result.invoice_errors,
-                    errors: None,
-                });
-            }
-            if result.transaction_id.is_none() {
-                return Err(Error::PayerRequired);
-            }
+        let commitment = Some(commitment.unwrap_or(self.commitment));
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+
+            // Signing with a new blockhash clears any existing signatures.
+            let hash = self.internal.tx.get_recent_blockhash().await?;
+            partial_sign(tx, signers, &hash);
+
+            let mut remote_signed = false;
+            if Self::needs_subsidizer_signature(tx) {
+                let result = self.internal.tx.sign_transaction(tx, invoice_list).await?;
+
+                // Error
+                if result.invoice_errors.is_some() {
+                    return Ok(SubmitTransactionResult {
+                        tx_id: None,
+                        invoice_errors: result.invoice_errors,
+                        errors: None,
+                        attempts,
+                    });
+                }
+                if result.transaction_id.is_none() {
+                    return Err(Error::PayerRequired);
+                }
 
-            // Success
-            if let Some(id) = result.transaction_id {
-                remote_signed = true;
-                tx.signatures[0] = Signature::new(&id);
+                // Success
+                if let Some(id) = result.transaction_id {
+                    remote_signed = true;
+                    tx.signatures[0] = id
+                        .to_signature()
+                        .ok_or(Error::MalformedResponse("invalid signature"))?;
+                }
             }
-        }
 
-        let result = self
-            .internal
-            .tx
-            .submit_transaction(tx, invoice_list, commitment, dedupe_id)
-            .await?;
+            let mut result = self
+                .internal
+                .tx
+                .submit_transaction(tx, invoice_list, commitment, dedupe_id)
+                .await?;
+            result.attempts = attempts;
+
+            if let Some(errors) = &result.errors {
+                if let Some(TransactionError::BadNonce(_)) = &errors.tx_error {
+                    self.internal.tx.invalidate_blockhash();
+
+                    // The blockhash may not have changed, in which case the remote signature would
+                    // not be cleared when signing again.
+                    if remote_signed {
+                        tx.signatures[0] = Signature::new(&[0; SIGNATURE_BYTES]);
+                    }
 
-        if let Some(errors) = &result.errors {
-            if let Some(TransactionError::BadNonce(_)) = &errors.tx_error {
-                if remote_signed {
-                    tx.signatures[0] = Signature::new(&[0; SIGNATURE_BYTES]);
-                }
+                    if attempts < self.max_nonce_attempts {
+                        continue;
+                    }
 
-                return Err(TransactionError::BadNonce(None).into());
+                    return Err(TransactionError::BadNonce(result.tx_id).into());
+                }
             }
-        }
 
-        Ok(result)
+            return Ok(result);
+        }
     }
 
     fn needs_subsidizer_signature(tx: &SolanaTransaction) -> bool {
@@ -317,20 +533,25 @@ impl Client {
     }
 
     fn get_merge_instructions(
-        account_infos: &[account_pb::AccountInfo],
+        account_infos: &[AccountInfo],
         dest: &SolanaPublicKey,
         owner: &SolanaPublicKey,
         funder: &SolanaPublicKey,
-    ) -> Vec<Instruction> {
+    ) -> Result<Vec<Instruction>> {
         let mut instructions = Vec::new();
 
         for info in account_infos {
-            let account = SolanaPublicKey::new(&info.account_id.as_ref().unwrap().value);
+            let account = info.account_id.to_solana_key();
 
             if account.eq(dest) {
                 continue;
             }
 
+            let balance = info
+                .balance
+                .try_into()
+                .map_err(|_| Error::MalformedResponse("negative account balance"))?;
+
             instructions.push(
                 spl_token::instruction::transfer(
                     &spl_token::id(),
@@ -338,19 +559,19 @@ impl Client {
                     dest,
                     owner,
                     &[],
-                    info.balance.try_into().unwrap(),
+                    balance,
                 )
                 .unwrap(),
             );
 
             // If no close authority is set, it likely means we
             // do not know it and can't make any assumptions.
-            if info.close_authority.is_none() {
-                continue;
-            }
+            let close_auth = match &info.close_authority {
+                Some(close_authority) => close_authority.to_solana_key(),
+                None => continue,
+            };
 
             let mut should_close = false;
-            let close_auth = SolanaPublicKey::new(&info.close_authority.as_ref().unwrap().value);
             for auth in [owner, funder] {
                 if auth.eq(&close_auth) {
                     should_close = true;
@@ -372,24 +593,125 @@ impl Client {
             }
         }
 
-        instructions
+        Ok(instructions)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::retry::{
+        is_transient,
+        strategy::{Limit, RetryOn},
+    };
     use crate::utils::kin_to_quarks;
     use crate::{
         client::{account_resolution::AccountResolution, environment::Environment},
+        gen::kin::agora::{
+            account::v4 as account_pb, common::v4 as model_pb_v4, transaction::v4 as tx_pb,
+        },
         key::private::PrivateKey,
+        model::{earn::Earn, event::Event, invoice::InvoiceList, memo::Memo},
         model::{invoice::Invoice, transaction_type::TransactionType},
+        test_utils::{server::MockServer, simulator::Simulator},
     };
+    use futures::StreamExt;
+    use solana_sdk::packet::PACKET_DATA_SIZE;
+    use spl_token::instruction::TokenInstruction;
+    use tonic::{Code, Status};
+
+    const SUBSIDIZER_SIGNATURE: [u8; SIGNATURE_BYTES] = [1; SIGNATURE_BYTES];
+
+    /// Starts a mock server that co-signs every transaction and accepts every submission.
+    async fn start_server() -> MockServer {
+        let server = MockServer::start().await;
+
+        server.sign_transaction.respond_with(|_| {
+            Ok(tx_pb::SignTransactionResponse {
+                result: tx_pb::sign_transaction_response::Result::Ok as i32,
+                signature: Some(model_pb_v4::TransactionSignature {
+                    value: SUBSIDIZER_SIGNATURE.to_vec(),
+                }),
+                invoice_errors: Vec::new(),
+            })
+        });
+        server.submit_transaction.respond_with(|req| {
+            Ok(tx_pb::SubmitTransactionResponse {
+                result: tx_pb::submit_transaction_response::Result::Ok as i32,
+                signature: Some(model_pb_v4::TransactionSignature {
+                    value: decode_tx(req).signatures[0].as_ref().to_vec(),
+                }),
+                transaction_error: None,
+                invoice_errors: Vec::new(),
+            })
+        });
+
+        server
+    }
+
+    fn decode_tx(req: &tx_pb::SubmitTransactionRequest) -> SolanaTransaction {
+        bincode::deserialize(&req.transaction.as_ref().unwrap().value).unwrap()
+    }
+
+    /// Waits until the server has the given number of open event streams.
+    async fn wait_for_event_streams(server: &MockServer, count: usize) {
+        while server.watched_accounts().len() != count {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+
+    /// Returns an event containing a transfer transaction with the given signature.
+    fn transfer_event(signature: u8, amount: u64) -> account_pb::Event {
+        let owner = PrivateKey::rand().public_key().to_solana_key();
+        let transfer = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &PrivateKey::rand().public_key().to_solana_key(),
+            &PrivateKey::rand().public_key().to_solana_key(),
+            &owner,
+            &[],
+            amount,
+        )
+        .unwrap();
+        let mut tx = SolanaTransaction::new_with_payer(&[transfer], Some(&owner));
+        tx.signatures[0] = Signature::new(&[signature; SIGNATURE_BYTES]);
+
+        account_pb::Event {
+            r#type: Some(account_pb::event::Type::TransactionEvent(
+                account_pb::TransactionEvent {
+                    transaction: Some(model_pb_v4::Transaction {
+                        value: bincode::serialize(&tx).unwrap(),
+                    }),
+                    transaction_error: None,
+                },
+            )),
+        }
+    }
+
+    #[tokio::test]
+    async fn client_is_thread_safe() {
+        fn assert_send<T: Send>(_: T) {}
+        fn assert_thread_safe<T: Clone + Send + Sync>() {}
+
+        assert_thread_safe::<Client>();
+
+        // The futures returned by the client must be Send to be spawned onto a runtime.
+        let client = Client::new(Environment::Test, None).await.unwrap();
+        let key = PrivateKey::rand();
+        assert_send(client.create_account(&key, None, None));
+        assert_send(client.get_balance(&key.public_key(), None, None));
+        assert_send(client.submit_payment(
+            Payment::new(key, key.public_key(), TransactionType::Spend, 1),
+            None,
+            None,
+            None,
+            None,
+        ));
+    }
 
     #[tokio::test]
     async fn create_account() {
         let key = PrivateKey::rand();
-        let mut client = Client::new(Environment::Test, Some(2)).await;
+        let client = Client::new(Environment::Test, Some(2)).await.unwrap();
 
         client.create_account(&key, None, None).await.unwrap();
     }
@@ -397,7 +719,7 @@ mod tests {
     #[tokio::test]
     async fn get_balance_with_resolution() {
         let key = PrivateKey::rand();
-        let mut client = Client::new(Environment::Test, Some(2)).await;
+        let client = Client::new(Environment::Test, Some(2)).await.unwrap();
 
         client.create_account(&key, None, None).await.unwrap();
 
@@ -411,10 +733,13 @@ mod tests {
     #[tokio::test]
     async fn airdrop() {
         let key = PrivateKey::rand();
-        let mut client = Client::new(Environment::Test, Some(2)).await;
+        let client = Client::new(Environment::Test, Some(2)).await.unwrap();
 
         client.create_account(&key, None, None).await.unwrap();
-        let accounts = client.resolve_token_accounts(&key.public_key()).await;
+        let accounts = client
+            .resolve_token_accounts(&key.public_key())
+            .await
+            .unwrap();
 
         let kin = "1234.555";
         client
@@ -431,14 +756,17 @@ mod tests {
 
     #[tokio::test]
     async fn submit_payment() {
-        let mut client = Client::new(Environment::Test, Some(100)).await;
+        let client = Client::new(Environment::Test, Some(100)).await.unwrap();
         let sender = PrivateKey::rand();
         let dest = PrivateKey::rand();
 
         client.create_account(&sender, None, None).await.unwrap();
         client.create_account(&dest, None, None).await.unwrap();
 
-        let sender_accounts = client.resolve_token_accounts(&sender.public_key()).await;
+        let sender_accounts = client
+            .resolve_token_accounts(&sender.public_key())
+            .await
+            .unwrap();
         let airdrop_kin = "10";
         client
             .request_airdrop(&sender_accounts[0], kin_to_quarks(airdrop_kin), None)
@@ -457,11 +785,10 @@ mod tests {
             None,
             None,
         ));
-        let _tx_id = client
+        let _receipt = client
             .submit_payment(payment, None, None, None, None)
             .await
-            .unwrap()
-            .unwrap_or_default();
+            .unwrap();
 
         let sender_balance = client
             .get_balance(&sender.public_key(), None, None)
@@ -474,4 +801,1015 @@ mod tests {
         assert_eq!(sender_balance, kin_to_quarks("6") as i64);
         assert_eq!(dest_balance, kin_to_quarks("4") as i64);
     }
+
+    #[tokio::test]
+    async fn submit_payment_with_mock() {
+        let server = start_server().await;
+        let client = server.client();
+        let sender = PrivateKey::rand();
+        let dest = PrivateKey::rand().public_key();
+
+        let tx_id = client
+            .submit_payment(
+                Payment::new(sender, dest, TransactionType::Spend, 10),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+            .tx_id;
+        assert_eq!(tx_id.as_bytes(), SUBSIDIZER_SIGNATURE);
+
+        let submitted = server.submit_transaction.requests();
+        assert_eq!(submitted.len(), 1);
+
+        let tx = decode_tx(&submitted[0]);
+        assert_eq!(tx.signatures[0].as_ref(), SUBSIDIZER_SIGNATURE);
+        assert_eq!(
+            tx.message.account_keys[0],
+            server.subsidizer.public_key().to_solana_key()
+        );
+        assert_eq!(tx.message.instructions.len(), 1);
+
+        let instruction = &tx.message.instructions[0];
+        assert_eq!(
+            TokenInstruction::unpack(&instruction.data).unwrap(),
+            TokenInstruction::Transfer { amount: 10 }
+        );
+        let accounts: Vec<SolanaPublicKey> = instruction
+            .accounts
+            .iter()
+            .map(|i| tx.message.account_keys[*i as usize])
+            .collect();
+        let sender_key = sender.public_key().to_solana_key();
+        assert_eq!(accounts, vec![sender_key, dest.to_solana_key(), sender_key]);
+    }
+
+    #[tokio::test]
+    async fn service_config_is_cached() {
+        let server = start_server().await;
+        let client = server.client();
+        let sender = PrivateKey::rand();
+        let dest = PrivateKey::rand().public_key();
+
+        for _ in 0..2 {
+            client
+                .submit_payment(
+                    Payment::new(sender, dest, TransactionType::Spend, 10),
+                    None,
+                    None,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+        }
+        assert_eq!(server.get_service_config.request_count(), 1);
+
+        client.refresh_service_config().await.unwrap();
+        assert_eq!(server.get_service_config.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn malformed_blockhash() {
+        let server = start_server().await;
+        let client = server.client();
+        let sender = PrivateKey::rand();
+
+        server
+            .get_recent_blockhash
+            .push_response(Ok(tx_pb::GetRecentBlockhashResponse {
+                blockhash: Some(model_pb_v4::Blockhash { value: vec![1; 16] }),
+            }));
+
+        match client
+            .submit_payment(
+                Payment::new(sender, sender.public_key(), TransactionType::Spend, 10),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+        {
+            Err(Error::MalformedResponse(_)) => (),
+            result => panic!("expected MalformedResponse, got {:?}", result),
+        }
+        assert_eq!(server.submit_transaction.request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn malformed_subsidizer_signature() {
+        let server = start_server().await;
+        let client = server.client();
+        let sender = PrivateKey::rand();
+
+        server
+            .sign_transaction
+            .push_response(Ok(tx_pb::SignTransactionResponse {
+                result: tx_pb::sign_transaction_response::Result::Ok as i32,
+                signature: Some(model_pb_v4::TransactionSignature { value: vec![1; 32] }),
+                invoice_errors: Vec::new(),
+            }));
+
+        match client
+            .submit_payment(
+                Payment::new(sender, sender.public_key(), TransactionType::Spend, 10),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+        {
+            Err(Error::MalformedResponse(_)) => (),
+            result => panic!("expected MalformedResponse, got {:?}", result),
+        }
+        assert_eq!(server.submit_transaction.request_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn resubmit_on_bad_nonce() {
+        let server = start_server().await;
+        let client = server.client();
+        let sender = PrivateKey::rand();
+
+        server
+            .submit_transaction
+            .push_response(Ok(tx_pb::SubmitTransactionResponse {
+                result: tx_pb::submit_transaction_response::Result::Failed as i32,
+                signature: Some(model_pb_v4::TransactionSignature {
+                    value: SUBSIDIZER_SIGNATURE.to_vec(),
+                }),
+                transaction_error: Some(model_pb_v4::TransactionError {
+                    reason: model_pb_v4::transaction_error::Reason::BadNonce as i32,
+                    instruction_index: 0,
+                    raw: Vec::new(),
+                }),
+                invoice_errors: Vec::new(),
+            }));
+
+        let receipt = client
+            .submit_payment(
+                Payment::new(sender, sender.public_key(), TransactionType::Spend, 10),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(receipt.attempts, 2);
+
+        // The cached blockhash is discarded, and the transaction is signed again with a new one.
+        let submitted = server.submit_transaction.requests();
+        assert_eq!(submitted.len(), 2);
+        assert_eq!(server.sign_transaction.request_count(), 2);
+        assert_eq!(server.get_recent_blockhash.request_count(), 2);
+        assert_ne!(
+            decode_tx(&submitted[0]).message.recent_blockhash,
+            decode_tx(&submitted[1]).message.recent_blockhash
+        );
+    }
+
+    #[tokio::test]
+    async fn bad_nonce_limit() {
+        let server = start_server().await;
+        let client = server
+            .client_builder()
+            .max_nonce_attempts(2)
+            .build()
+            .unwrap();
+        let sender = PrivateKey::rand();
+
+        for _ in 0..2 {
+            server
+                .submit_transaction
+                .push_response(Ok(tx_pb::SubmitTransactionResponse {
+                    result: tx_pb::submit_transaction_response::Result::Failed as i32,
+                    signature: Some(model_pb_v4::TransactionSignature {
+                        value: SUBSIDIZER_SIGNATURE.to_vec(),
+                    }),
+                    transaction_error: Some(model_pb_v4::TransactionError {
+                        reason: model_pb_v4::transaction_error::Reason::BadNonce as i32,
+                        instruction_index: 0,
+                        raw: Vec::new(),
+                    }),
+                    invoice_errors: Vec::new(),
+                }));
+        }
+
+        match client
+            .submit_payment(
+                Payment::new(sender, sender.public_key(), TransactionType::Spend, 10),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+        {
+            Err(Error::TransactionFailed(TransactionError::BadNonce(Some(tx_id)))) => {
+                assert_eq!(tx_id.to_vec(), SUBSIDIZER_SIGNATURE.to_vec())
+            }
+            result => panic!("expected BadNonce, got {:?}", result),
+        }
+        assert_eq!(server.submit_transaction.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn create_account_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client_builder().app_index(2).build().unwrap();
+        let key = PrivateKey::rand();
+
+        client.create_account(&key, None, None).await.unwrap();
+
+        let accounts = sim.token_accounts(&key.public_key());
+        assert_eq!(accounts.len(), 1);
+
+        let account = sim.token_account(&accounts[0]).unwrap();
+        assert_eq!(account.owner, key.public_key().to_solana_key());
+        assert_eq!(
+            account.close_authority,
+            Some(sim.subsidizer.public_key().to_solana_key())
+        );
+
+        match client.create_account(&key, None, None).await {
+            Err(Error::AccountExists) => (),
+            result => panic!("expected AccountExists, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn create_existing_account_after_transient_error_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let key = PrivateKey::rand();
+
+        // The account existed before the call, with a different close authority than the one the
+        // client would have set.
+        sim.create_associated_token_account(&key.public_key(), None);
+        sim.create_account
+            .push_response(Err(Status::unavailable("unavailable")));
+
+        match client.create_account(&key, None, None).await {
+            Err(Error::AccountExists) => (),
+            result => panic!("expected AccountExists, got {:?}", result),
+        }
+        assert_eq!(sim.create_account.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn create_account_after_lost_response_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let key = PrivateKey::rand();
+
+        // The first attempt creates the account, but its response is lost.
+        sim.create_associated_token_account(&key.public_key(), Some(&sim.subsidizer.public_key()));
+        sim.create_account
+            .push_response(Err(Status::unavailable("unavailable")));
+
+        client.create_account(&key, None, None).await.unwrap();
+        assert_eq!(sim.create_account.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_balance_with_resolution_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let key = PrivateKey::rand();
+
+        client.create_account(&key, None, None).await.unwrap();
+
+        let balance = client
+            .get_balance(&key.public_key(), None, Some(AccountResolution::Preferred))
+            .await
+            .unwrap();
+        assert_eq!(balance, 0);
+
+        match client
+            .get_balance(&key.public_key(), None, Some(AccountResolution::Exact))
+            .await
+        {
+            Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(_))) => (),
+            result => panic!("expected AccountDoesNotExist, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn airdrop_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let key = PrivateKey::rand();
+
+        client.create_account(&key, None, None).await.unwrap();
+        let accounts = client
+            .resolve_token_accounts(&key.public_key())
+            .await
+            .unwrap();
+
+        let kin = "1234.555";
+        client
+            .request_airdrop(&accounts[0], kin_to_quarks(kin), None)
+            .await
+            .unwrap();
+
+        let balance = client.get_balance(&accounts[0], None, None).await.unwrap();
+        assert_eq!(balance, kin_to_quarks(kin) as i64);
+    }
+
+    #[tokio::test]
+    async fn airdrop_is_not_retried() {
+        let server = MockServer::start().await;
+        let client = server.client();
+
+        server
+            .request_airdrop
+            .push_response(Err(Status::deadline_exceeded("timed out")));
+
+        match client
+            .request_airdrop(&PrivateKey::rand().public_key(), 10, None)
+            .await
+        {
+            Err(Error::Transport(status)) => assert_eq!(status.code(), Code::DeadlineExceeded),
+            result => panic!("expected a transport error, got {:?}", result),
+        }
+        assert_eq!(server.request_airdrop.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn submit_payment_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client_builder().app_index(100).build().unwrap();
+        let sender = PrivateKey::rand();
+        let dest = PrivateKey::rand();
+
+        client.create_account(&sender, None, None).await.unwrap();
+        client.create_account(&dest, None, None).await.unwrap();
+
+        let sender_account = sim.token_accounts(&sender.public_key())[0];
+        let dest_account = sim.token_accounts(&dest.public_key())[0];
+        sim.mint(&sender_account, kin_to_quarks("10"));
+
+        // Payments to the owner accounts are resolved to their token accounts.
+        let mut payment = Payment::new(
+            sender,
+            dest.public_key(),
+            TransactionType::Spend,
+            kin_to_quarks("4"),
+        );
+        payment.set_invoice(Invoice::new(
+            "TestPayment",
+            payment.quarks.try_into().unwrap(),
+            None,
+            None,
+        ));
+        let invoice_hash = InvoiceList::new(&[payment.invoice.clone().unwrap()]).get_sha244_hash();
+        let receipt = client
+            .submit_payment(payment, None, None, None, None)
+            .await
+            .unwrap();
+        let tx_id = receipt.tx_id.clone();
+
+        assert_eq!(sim.balance(&sender_account), Some(kin_to_quarks("6")));
+        assert_eq!(sim.balance(&dest_account), Some(kin_to_quarks("4")));
+
+        // The receipt records the resolved accounts and the memo.
+        assert_eq!(receipt.sender, sender_account);
+        assert_eq!(receipt.destination, dest_account);
+        assert!(!receipt.destination_created);
+        assert_eq!(receipt.fee_payer, sim.subsidizer.public_key());
+        assert_eq!(receipt.invoice_hash.as_ref(), Some(&invoice_hash));
+        assert_eq!(receipt.attempts, 1);
+        let memo = Memo::from_base64(receipt.memo.as_ref().unwrap(), true).unwrap();
+        assert_eq!(memo.app_index(), 100);
+        assert_eq!(memo.foreign_key()[..invoice_hash.len()], invoice_hash[..]);
+
+        let tx = client.get_transaction(&tx_id, None).await.unwrap();
+        assert_eq!(tx.payments.len(), 1);
+        assert_eq!(tx.payments[0].quarks, kin_to_quarks("4") as i64);
+        assert!(tx.payments[0].invoice.is_some());
+
+        // Payments that exceed the balance fail without being applied.
+        let payment = Payment::new(
+            sender,
+            dest_account,
+            TransactionType::Spend,
+            kin_to_quarks("7"),
+        );
+        match client.submit_payment(payment, None, None, None, None).await {
+            Err(Error::TransactionFailed(TransactionError::InsufficientBalance(_))) => (),
+            result => panic!("expected InsufficientBalance, got {:?}", result),
+        }
+        assert_eq!(sim.balance(&sender_account), Some(kin_to_quarks("6")));
+        assert_eq!(sim.history().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn merge_token_accounts_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let key = PrivateKey::rand();
+
+        let first = sim.create_token_account(&key.public_key(), 3);
+        let second = sim.create_token_account(&key.public_key(), 4);
+
+        client
+            .merge_token_accounts(&key, true, None, None)
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Both accounts are merged into a new associated token account, and closed.
+        let accounts = sim.token_accounts(&key.public_key());
+        assert_eq!(accounts.len(), 1);
+        assert_eq!(sim.balance(&accounts[0]), Some(7));
+        assert!(sim.token_account(&first).is_none());
+        assert!(sim.token_account(&second).is_none());
+
+        // There is nothing left to merge.
+        let tx_id = client
+            .merge_token_accounts(&key, true, None, None)
+            .await
+            .unwrap();
+        assert!(tx_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn watch_account_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let sender = PrivateKey::rand();
+        let dest = PrivateKey::rand();
+
+        client.create_account(&sender, None, None).await.unwrap();
+        client.create_account(&dest, None, None).await.unwrap();
+        let sender_account = sim.token_accounts(&sender.public_key())[0];
+        let dest_account = sim.token_accounts(&dest.public_key())[0];
+        sim.mint(&sender_account, 10);
+
+        let events = client.watch_account(&dest_account);
+        let received = tokio::spawn(events.take(2).collect::<Vec<_>>());
+        wait_for_event_streams(&sim, 1).await;
+
+        let tx_id = client
+            .submit_payment(
+                Payment::new(sender, dest_account, TransactionType::Spend, 4),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+            .tx_id;
+
+        let received = tokio::time::timeout(Duration::from_secs(5), received)
+            .await
+            .unwrap()
+            .unwrap();
+        match &received[0] {
+            Ok(Event::Transaction(data)) => {
+                assert_eq!(data.tx_id, tx_id);
+                assert_eq!(data.payments.len(), 1);
+                assert_eq!(data.payments[0].sender, sender_account);
+                assert_eq!(data.payments[0].destination, dest_account);
+                assert_eq!(data.payments[0].quarks, 4);
+            }
+            event => panic!("expected a transaction event, got {:?}", event),
+        }
+        match &received[1] {
+            Ok(Event::AccountUpdate(info)) => {
+                assert_eq!(info.account_id, dest_account);
+                assert_eq!(info.owner, Some(dest.public_key()));
+                assert_eq!(info.balance, 4);
+            }
+            event => panic!("expected an account update, got {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn watch_account_reconnects_without_duplicates() {
+        let server = MockServer::start().await;
+        let client = server.client();
+
+        let events = |events| {
+            Ok(vec![account_pb::Events {
+                result: account_pb::events::Result::Ok as i32,
+                events,
+            }])
+        };
+        server
+            .get_events
+            .push_response(events(vec![transfer_event(1, 10)]));
+        server
+            .get_events
+            .push_response(events(vec![transfer_event(1, 10), transfer_event(2, 20)]));
+
+        let stream = client.watch_account(&PrivateKey::rand().public_key());
+        let received = tokio::spawn(stream.take(2).collect::<Vec<_>>());
+
+        // The first stream is closed by the server after sending its events.
+        wait_for_event_streams(&server, 1).await;
+        server.close_event_streams();
+
+        let received = tokio::time::timeout(Duration::from_secs(5), received)
+            .await
+            .unwrap()
+            .unwrap();
+        let amounts: Vec<i64> = received
+            .iter()
+            .map(|event| match event {
+                Ok(Event::Transaction(data)) => data.payments[0].quarks,
+                event => panic!("expected a transaction event, got {:?}", event),
+            })
+            .collect();
+        assert_eq!(amounts, vec![10, 20]);
+        assert_eq!(server.get_events.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn watch_account_recovers_from_unavailable_server_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim
+            .client_builder()
+            .retry_strategies(vec![
+                Box::new(Limit { max_attempts: 2 }),
+                Box::new(RetryOn::new(is_transient)),
+            ])
+            .build()
+            .unwrap();
+        let sender = PrivateKey::rand();
+        let dest = PrivateKey::rand();
+
+        client.create_account(&sender, None, None).await.unwrap();
+        client.create_account(&dest, None, None).await.unwrap();
+        let sender_account = sim.token_accounts(&sender.public_key())[0];
+        let dest_account = sim.token_accounts(&dest.public_key())[0];
+        sim.mint(&sender_account, 10);
+
+        // The server is unavailable for longer than the retrier keeps retrying.
+        for _ in 0..6 {
+            sim.get_events
+                .push_response(Err(Status::unavailable("unavailable")));
+        }
+
+        let events = client.watch_account(&dest_account);
+        let received = tokio::spawn(events.take(1).collect::<Vec<_>>());
+        tokio::time::timeout(Duration::from_secs(10), wait_for_event_streams(&sim, 1))
+            .await
+            .unwrap();
+        assert_eq!(sim.get_events.request_count(), 7);
+
+        let tx_id = client
+            .submit_payment(
+                Payment::new(sender, dest_account, TransactionType::Spend, 4),
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap()
+            .tx_id;
+
+        let received = tokio::time::timeout(Duration::from_secs(5), received)
+            .await
+            .unwrap()
+            .unwrap();
+        match &received[0] {
+            Ok(Event::Transaction(data)) => assert_eq!(data.tx_id, tx_id),
+            event => panic!("expected a transaction event, got {:?}", event),
+        }
+    }
+
+    #[tokio::test]
+    async fn watch_account_skips_malformed_events() {
+        let server = MockServer::start().await;
+        let client = server.client();
+
+        let mut malformed = transfer_event(1, 10);
+        if let Some(account_pb::event::Type::TransactionEvent(event)) = &mut malformed.r#type {
+            event.transaction = Some(model_pb_v4::Transaction {
+                value: vec![1, 2, 3],
+            });
+        }
+        server.get_events.push_response(Ok(vec![
+            account_pb::Events {
+                result: account_pb::events::Result::Ok as i32,
+                events: vec![malformed],
+            },
+            account_pb::Events {
+                result: account_pb::events::Result::Ok as i32,
+                events: vec![transfer_event(2, 20)],
+            },
+        ]));
+
+        let mut events = client.watch_account(&PrivateKey::rand().public_key());
+        assert!(matches!(
+            events.next().await,
+            Some(Err(Error::MalformedResponse(_)))
+        ));
+        match events.next().await {
+            Some(Ok(Event::Transaction(data))) => assert_eq!(data.payments[0].quarks, 20),
+            event => panic!("expected a transaction event, got {:?}", event),
+        }
+        assert_eq!(server.get_events.request_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn watch_missing_account() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+
+        let mut events = client.watch_account(&PrivateKey::rand().public_key());
+        match events.next().await {
+            Some(Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(_)))) => (),
+            event => panic!("expected AccountDoesNotExist, got {:?}", event),
+        }
+        assert!(events.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn get_history_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let sender = PrivateKey::rand();
+        let dest = PrivateKey::rand();
+
+        client.create_account(&sender, None, None).await.unwrap();
+        client.create_account(&dest, None, None).await.unwrap();
+        let sender_account = sim.token_accounts(&sender.public_key())[0];
+        let dest_account = sim.token_accounts(&dest.public_key())[0];
+        sim.mint(&sender_account, 10);
+        sim.set_history_page_size(2);
+
+        for quarks in 1..=3 {
+            let payment = Payment::new(sender, dest_account, TransactionType::Spend, quarks);
+            client
+                .submit_payment(payment, None, None, None, None)
+                .await
+                .unwrap();
+        }
+
+        // The history of the destination has the creation of its account, followed by the payments.
+        let amounts = |page: &HistoryPage| -> Vec<i64> {
+            page.transactions
+                .iter()
+                .map(|tx| tx.payments.first().map_or(0, |p| p.quarks))
+                .collect()
+        };
+
+        let first = client.get_history(&dest_account, None, None).await.unwrap();
+        assert_eq!(amounts(&first), vec![0, 1]);
+
+        let second = client
+            .get_history(&dest_account, first.next_cursor.as_deref(), None)
+            .await
+            .unwrap();
+        assert_eq!(amounts(&second), vec![2, 3]);
+
+        let last = client
+            .get_history(&dest_account, second.next_cursor.as_deref(), None)
+            .await
+            .unwrap();
+        assert!(last.transactions.is_empty());
+        assert!(last.next_cursor.is_none());
+
+        let newest = client
+            .get_history(&dest_account, None, Some(Direction::Descending))
+            .await
+            .unwrap();
+        assert_eq!(amounts(&newest), vec![3, 2]);
+
+        let all: Vec<TransactionData> = client
+            .history(&dest_account, Some(Direction::Descending))
+            .map(|tx| tx.unwrap())
+            .collect()
+            .await;
+        assert_eq!(all.len(), 4);
+        assert_eq!(all[0].payments[0].quarks, 3);
+        assert!(all[3].payments.is_empty());
+
+        match client
+            .get_history(&PrivateKey::rand().public_key(), None, None)
+            .await
+        {
+            Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(_))) => (),
+            result => panic!("expected AccountDoesNotExist, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_account_info_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let key = PrivateKey::rand();
+
+        client.create_account(&key, None, None).await.unwrap();
+        let other = sim.create_token_account(&key.public_key(), 5);
+
+        let infos = client
+            .resolve_token_account_infos(&key.public_key())
+            .await
+            .unwrap();
+        assert_eq!(infos.len(), 2);
+        assert_eq!(infos[1].account_id, other);
+
+        let subsidizer = sim.subsidizer.public_key();
+        for info in &infos {
+            assert_eq!(info.owner, Some(key.public_key()));
+            assert_eq!(info.close_authority, Some(subsidizer));
+        }
+
+        let info = client.get_account_info(&other, None).await.unwrap();
+        assert_eq!(info, infos[1]);
+        assert_eq!(info.balance, 5);
+
+        match client.get_account_info(&key.public_key(), None).await {
+            Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(_))) => (),
+            result => panic!("expected AccountDoesNotExist, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_aggregate_balance_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let key = PrivateKey::rand();
+
+        client.create_account(&key, None, None).await.unwrap();
+        let assoc = sim.token_accounts(&key.public_key())[0];
+        sim.mint(&assoc, 1);
+        sim.create_token_account(&key.public_key(), 2);
+        let other = sim.create_token_account(&key.public_key(), 3);
+
+        let balance = client
+            .get_aggregate_balance(&key.public_key())
+            .await
+            .unwrap();
+        assert_eq!(balance.total, 6);
+        let balances: Vec<i64> = balance.accounts.iter().map(|a| a.balance).collect();
+        assert_eq!(balances, vec![1, 2, 3]);
+
+        // A token account on its own has only its own balance.
+        let balance = client.get_aggregate_balance(&other).await.unwrap();
+        assert_eq!(balance.total, 3);
+        assert_eq!(balance.accounts.len(), 1);
+
+        match client
+            .get_aggregate_balance(&PrivateKey::rand().public_key())
+            .await
+        {
+            Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(_))) => (),
+            result => panic!("expected AccountDoesNotExist, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn get_balances_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client_builder().balance_concurrency(2).build().unwrap();
+
+        let mut accounts = Vec::new();
+        for quarks in 1..=4 {
+            accounts.push(sim.create_token_account(&PrivateKey::rand().public_key(), quarks));
+        }
+
+        // An owner is resolved to its token account.
+        let owner = PrivateKey::rand();
+        client.create_account(&owner, None, None).await.unwrap();
+        sim.mint(&sim.token_accounts(&owner.public_key())[0], 5);
+        accounts.push(owner.public_key());
+
+        accounts.push(PrivateKey::rand().public_key());
+
+        let balances = client.get_balances(&accounts, None, None).await;
+        assert_eq!(balances.len(), 6);
+        for (i, balance) in balances[..5].iter().enumerate() {
+            assert_eq!(*balance.as_ref().unwrap(), i as i64 + 1);
+        }
+        match &balances[5] {
+            Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(_))) => (),
+            result => panic!("expected AccountDoesNotExist, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn submit_earn_batch_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client_builder().app_index(1).build().unwrap();
+        let sender = PrivateKey::rand();
+
+        client.create_account(&sender, None, None).await.unwrap();
+        let sender_account = sim.token_accounts(&sender.public_key())[0];
+        sim.mint(&sender_account, 60);
+
+        let earns: Vec<Earn> = (0..60)
+            .map(|_| {
+                let dest = sim.create_token_account(&PrivateKey::rand().public_key(), 0);
+                let mut earn = Earn::new(dest, 1);
+                earn.set_invoice(Invoice::new("Earn", 1, None, None));
+                earn
+            })
+            .collect();
+
+        // The earns are split across multiple transactions, each within the size limit.
+        let result = client
+            .submit_earn_batch(EarnBatch::new(sender, earns.clone()), None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.succeeded.len(), 60);
+        assert!(result.failed.is_empty());
+        assert!(result.succeeded.iter().all(|r| r.attempts == 1));
+        for earn in &earns {
+            assert_eq!(sim.balance(&earn.destination), Some(1));
+        }
+
+        let requests = sim.submit_transaction.requests();
+        assert!(requests.len() > 1);
+        for req in &requests {
+            assert!(req.transaction.as_ref().unwrap().value.len() <= PACKET_DATA_SIZE);
+        }
+
+        let first_tx_id = result.succeeded[0].tx_id.clone().unwrap();
+        let first_len = result
+            .succeeded
+            .iter()
+            .filter(|r| r.tx_id.as_ref() == Some(&first_tx_id))
+            .count();
+        assert!(first_len < 30);
+
+        // Submission stops at the first transaction that fails.
+        sim.mint(&sender_account, first_len as u64 + 1);
+        let earns: Vec<Earn> = earns.iter().map(|e| Earn::new(e.destination, 1)).collect();
+        let result = client
+            .submit_earn_batch(EarnBatch::new(sender, earns), None, None)
+            .await
+            .unwrap();
+        assert_eq!(result.succeeded.len(), first_len);
+        assert_eq!(result.failed.len(), 60 - first_len);
+        assert_eq!(sim.balance(&sender_account), Some(1));
+
+        let attempted = result.failed.iter().filter(|r| r.error.is_some()).count();
+        assert!(attempted > 0 && attempted < result.failed.len());
+        for r in &result.failed[..attempted] {
+            assert!(matches!(
+                r.error.as_deref(),
+                Some(Error::TransactionFailed(
+                    TransactionError::InsufficientBalance(_)
+                ))
+            ));
+        }
+
+        // Invoices must be provided for all of the earns, or none of them.
+        let mut earns = vec![Earn::new(sender_account, 1); 2];
+        earns[0].set_invoice(Invoice::new("Earn", 1, None, None));
+        match client
+            .submit_earn_batch(EarnBatch::new(sender, earns), None, None)
+            .await
+        {
+            Err(Error::InvalidEarnBatch(_)) => (),
+            result => panic!("expected InvalidEarnBatch, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn submit_earn_batch_invoice_errors() {
+        let sim = Simulator::start().await;
+        let client = sim.client_builder().app_index(1).build().unwrap();
+        let sender = PrivateKey::rand();
+        let sender_account = sim.create_token_account(&sender.public_key(), 3);
+
+        let earns: Vec<Earn> = (0..3)
+            .map(|i| {
+                let dest = sim.create_token_account(&PrivateKey::rand().public_key(), 0);
+                let mut earn = Earn::new(dest, 1);
+                earn.set_invoice(Invoice::new(&format!("Earn {}", i), 1, None, None));
+                earn
+            })
+            .collect();
+
+        sim.sign_transaction
+            .push_response(Ok(tx_pb::SignTransactionResponse {
+                result: tx_pb::sign_transaction_response::Result::InvoiceError as i32,
+                signature: None,
+                invoice_errors: vec![model_pb_v3::InvoiceError {
+                    op_index: 1,
+                    reason: model_pb_v3::invoice_error::Reason::AlreadyPaid as i32,
+                    ..Default::default()
+                }],
+            }));
+
+        let result = client
+            .submit_earn_batch(EarnBatch::new(sender, earns.clone()), None, None)
+            .await
+            .unwrap();
+        assert!(result.succeeded.is_empty());
+        assert!(result.failed[0].error.is_none());
+        assert!(matches!(
+            result.failed[1].error.as_deref(),
+            Some(Error::AlreadyPaid)
+        ));
+        assert!(result.failed[2].error.is_none());
+        assert_eq!(sim.balance(&sender_account), Some(3));
+
+        // The invoice list has an invoice per transfer, and its hash is the memo foreign key.
+        let req = &sim.sign_transaction.requests()[0];
+        let invoice_list = req.invoice_list.clone().unwrap();
+        assert_eq!(invoice_list.invoices.len(), 3);
+
+        let tx: SolanaTransaction =
+            bincode::deserialize(&req.transaction.as_ref().unwrap().value).unwrap();
+        let memo = Memo::from_base64(
+            std::str::from_utf8(&tx.message.instructions[0].data).unwrap(),
+            true,
+        )
+        .unwrap();
+        let hash = InvoiceList::from_proto(invoice_list).get_sha244_hash();
+        assert_eq!(memo.foreign_key()[..hash.len()], hash[..]);
+    }
+
+    #[tokio::test]
+    async fn confirm_transaction_with_mock() {
+        use tx_pb::get_transaction_response::State;
+
+        let server = MockServer::start().await;
+        let client = server
+            .client_builder()
+            .confirmation_poll_interval(Duration::from_millis(5))
+            .build()
+            .unwrap();
+
+        let response = |state: State| tx_pb::GetTransactionResponse {
+            state: state as i32,
+            ..Default::default()
+        };
+        let tx_id = TransactionId::new(&[1; 64]);
+        server
+            .get_transaction
+            .push_response(Ok(response(State::Unknown)));
+        server
+            .get_transaction
+            .push_response(Ok(response(State::Pending)));
+        server
+            .get_transaction
+            .push_response(Ok(response(State::Success)));
+
+        let tx = client
+            .confirm_transaction(&tx_id, Some(Commitment::Max), Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(tx.tx_id, tx_id);
+        assert_eq!(tx.tx_state, TransactionState::Success);
+
+        let requests = server.get_transaction.requests();
+        assert_eq!(requests.len(), 3);
+        for req in requests {
+            assert_eq!(req.commitment, Commitment::Max as i32);
+        }
+
+        // Transactions that never reach a final state time out.
+        server
+            .get_transaction
+            .respond_with(move |_| Ok(response(State::Pending)));
+        match client
+            .confirm_transaction(&tx_id, None, Duration::from_millis(50))
+            .await
+        {
+            Err(Error::ConfirmationTimeout) => (),
+            result => panic!("expected ConfirmationTimeout, got {:?}", result),
+        }
+    }
+
+    #[tokio::test]
+    async fn submit_payment_creates_destination_simulated() {
+        let sim = Simulator::start().await;
+        let client = sim.client();
+        let sender = PrivateKey::rand();
+        let dest = PrivateKey::rand().public_key();
+
+        let sender_account = sim.create_token_account(&sender.public_key(), 10);
+
+        let mut payment = Payment::new(sender, dest, TransactionType::Spend, 4);
+        payment.set_memo("created");
+        let receipt = client
+            .submit_payment(payment, None, None, None, Some(true))
+            .await
+            .unwrap();
+
+        assert_eq!(receipt.sender, sender_account);
+        assert!(receipt.destination_created);
+        assert_ne!(receipt.destination, dest);
+        assert!(receipt.invoice_hash.is_none());
+        assert_eq!(receipt.memo.as_deref(), Some("created"));
+
+        let created = sim.token_account(&receipt.destination).unwrap();
+        assert_eq!(created.owner, dest.to_solana_key());
+        assert_eq!(created.balance, 4);
+    }
 }
@@ -0,0 +1,290 @@
+use {
+    crate::{
+        client::{
+            account_resolution::AccountResolution,
+            client::Client,
+            internal::transaction::SubmitTransactionResult,
+            {get_signers_and_funder, invoice_foreign_key, kin_memo_instruction},
+        },
+        error::{Error, TransactionError},
+        gen::kin::agora::common::v3 as model_pb_v3,
+        key::{private::PrivateKey, public::PublicKey},
+        model::{
+            earn::{Earn, EarnBatch, EarnBatchResult, EarnResult},
+            transaction_id::TransactionId,
+            transaction_type::TransactionType,
+        },
+        solana::{
+            commitment::Commitment,
+            memo::program::{MemoParams, MemoProgram},
+        },
+    },
+    solana_sdk::{
+        packet::PACKET_DATA_SIZE, pubkey::Pubkey as SolanaPublicKey,
+        transaction::Transaction as SolanaTransaction,
+    },
+    std::{ops::Range, sync::Arc},
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+pub async fn submit_earn_batch(
+    client: &Client,
+    batch: &EarnBatch,
+    commitment: Option<Commitment>,
+    sender_resolution: Option<AccountResolution>,
+) -> Result<EarnBatchResult> {
+    let invoice_count = batch.earns.iter().filter(|e| e.invoice.is_some()).count();
+    if invoice_count > 0 {
+        if client.app_index == 0 {
+            return Err(Error::AppIndexRequired);
+        }
+        if invoice_count != batch.earns.len() {
+            return Err(Error::InvalidEarnBatch(
+                "either all or none of the earns must have an invoice",
+            ));
+        }
+        if batch.memo.is_some() {
+            return Err(Error::InvalidEarnBatch(
+                "invoices cannot be combined with a memo",
+            ));
+        }
+    }
+
+    let config = client.internal.tx.get_service_config().await?;
+    let (signers, funder) =
+        get_signers_and_funder(&batch.sender, batch.subsidizer.as_ref(), &config)?;
+
+    // The earns are split as if the source differs from the sender, so that the transactions still
+    // fit if the sender is resolved to one of its token accounts.
+    let chunks = chunk_earns(client, batch, &PrivateKey::rand().public_key(), &funder)?;
+    let mut source = batch.sender.public_key();
+
+    let mut batch_result = EarnBatchResult::default();
+    let mut earns = batch.earns.iter().cloned();
+    let mut resolved = sender_resolution.unwrap_or_default() != AccountResolution::Preferred;
+
+    for chunk in chunks {
+        let chunk_earns = &batch.earns[chunk.clone()];
+
+        let mut result = submit_chunk(
+            client,
+            batch,
+            chunk_earns,
+            &source,
+            &funder,
+            &signers,
+            commitment,
+        )
+        .await;
+
+        if !resolved && is_account_not_found(&result) {
+            let accounts = client
+                .resolve_token_accounts(&batch.sender.public_key())
+                .await?;
+            if !accounts.is_empty() {
+                source = accounts[0];
+                result = submit_chunk(
+                    client,
+                    batch,
+                    chunk_earns,
+                    &source,
+                    &funder,
+                    &signers,
+                    commitment,
+                )
+                .await;
+            }
+        }
+        // Only the first transaction can reveal that the sender needs to be resolved.
+        resolved = true;
+
+        let (errors, attempts) = match chunk_outcome(result, chunk.len()) {
+            ChunkOutcome::Submitted(tx_id, attempts) => {
+                for earn in earns.by_ref().take(chunk.len()) {
+                    batch_result.succeeded.push(EarnResult {
+                        earn,
+                        tx_id: Some(tx_id.clone()),
+                        error: None,
+                        attempts,
+                    });
+                }
+                continue;
+            }
+            ChunkOutcome::Failed(errors, attempts) => (errors, attempts),
+        };
+
+        for (earn, error) in earns.by_ref().take(chunk.len()).zip(errors) {
+            batch_result.failed.push(EarnResult {
+                earn,
+                tx_id: None,
+                error,
+                attempts,
+            });
+        }
+        break;
+    }
+
+    for earn in earns {
+        batch_result.failed.push(EarnResult {
+            earn,
+            tx_id: None,
+            error: None,
+            attempts: 0,
+        });
+    }
+
+    Ok(batch_result)
+}
+
+/// Splits the earns of a batch into ranges that each fit in a single transaction.
+fn chunk_earns(
+    client: &Client,
+    batch: &EarnBatch,
+    source: &PublicKey,
+    funder: &SolanaPublicKey,
+) -> Result<Vec<Range<usize>>> {
+    let mut chunks = Vec::new();
+
+    let mut start = 0;
+    for end in 1..=batch.earns.len() {
+        let (tx, _) = earn_tx(client, batch, &batch.earns[start..end], source, funder);
+        if bincode::serialized_size(&tx).unwrap() as usize <= PACKET_DATA_SIZE {
+            continue;
+        }
+
+        if end - start == 1 {
+            return Err(Error::InvalidEarnBatch(
+                "earn does not fit in a transaction",
+            ));
+        }
+        chunks.push(start..end - 1);
+        start = end - 1;
+    }
+
+    if start < batch.earns.len() {
+        chunks.push(start..batch.earns.len());
+    }
+
+    Ok(chunks)
+}
+
+fn earn_tx(
+    client: &Client,
+    batch: &EarnBatch,
+    earns: &[Earn],
+    source: &PublicKey,
+    funder: &SolanaPublicKey,
+) -> (SolanaTransaction, Option<model_pb_v3::InvoiceList>) {
+    let mut instructions = Vec::new();
+    let mut invoice_list_proto: Option<model_pb_v3::InvoiceList> = None;
+
+    if let Some(string_memo) = &batch.memo {
+        instructions.push(MemoProgram::memo(MemoParams::new(string_memo.to_string())))
+    } else if client.app_index > 0 {
+        let invoices: Vec<_> = earns.iter().filter_map(|e| e.invoice.clone()).collect();
+        let (foreign_key, invoice_list) = invoice_foreign_key(&invoices);
+        invoice_list_proto = invoice_list;
+
+        instructions.push(kin_memo_instruction(
+            TransactionType::Earn,
+            client.app_index,
+            &foreign_key,
+        ));
+    }
+
+    let owner = batch.sender.public_key().to_solana_key();
+    for earn in earns {
+        instructions.push(
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &source.to_solana_key(),
+                &earn.destination.to_solana_key(),
+                &owner,
+                &[],
+                earn.quarks,
+            )
+            .unwrap(),
+        );
+    }
+
+    (
+        SolanaTransaction::new_with_payer(&instructions, Some(funder)),
+        invoice_list_proto,
+    )
+}
+
+async fn submit_chunk(
+    client: &Client,
+    batch: &EarnBatch,
+    earns: &[Earn],
+    source: &PublicKey,
+    funder: &SolanaPublicKey,
+    signers: &[&PrivateKey],
+    commitment: Option<Commitment>,
+) -> Result<SubmitTransactionResult> {
+    let (mut tx, invoice_list) = earn_tx(client, batch, earns, source, funder);
+
+    client
+        .sign_and_submit_tx(signers, &mut tx, commitment, invoice_list.as_ref(), None)
+        .await
+}
+
+fn is_account_not_found(result: &Result<SubmitTransactionResult>) -> bool {
+    matches!(
+        result,
+        Ok(SubmitTransactionResult {
+            errors: Some(errors),
+            ..
+        }) if matches!(errors.tx_error, Some(TransactionError::AccountDoesNotExist(_)))
+    )
+}
+
+/// The outcome of a chunk, with the number of times its transaction was submitted.
+enum ChunkOutcome {
+    Submitted(TransactionId, u32),
+
+    /// The transaction failed, with the error of each earn in it.
+    Failed(Vec<Option<Arc<Error>>>, u32),
+}
+
+fn chunk_outcome(result: Result<SubmitTransactionResult>, len: usize) -> ChunkOutcome {
+    let result = match result {
+        Ok(result) => result,
+        Err(e) => return ChunkOutcome::Failed(vec![Some(Arc::new(e)); len], 0),
+    };
+
+    // Invoice errors identify the earns that were rejected; the rest were not submitted.
+    if let Some(invoice_errors) = result.invoice_errors.filter(|e| !e.is_empty()) {
+        let errors = match Error::from_invoice_errors(&invoice_errors, len) {
+            Ok(errors) => errors.into_iter().map(|e| e.map(Arc::new)).collect(),
+            Err(e) => vec![Some(Arc::new(e)); len],
+        };
+
+        return ChunkOutcome::Failed(errors, result.attempts);
+    }
+
+    if let Some(errors) = result.errors {
+        if let Some(tx_error) = errors.tx_error {
+            let payment_errors = errors.payment_errors.unwrap_or_default();
+            let tx_error = Arc::new(Error::from(tx_error));
+
+            let errors = (0..len)
+                .map(|i| match payment_errors.get(i) {
+                    Some(Some(e)) => Some(Arc::new(e.clone().into())),
+                    _ => Some(tx_error.clone()),
+                })
+                .collect();
+
+            return ChunkOutcome::Failed(errors, result.attempts);
+        }
+    }
+
+    match result.tx_id {
+        Some(tx_id) => ChunkOutcome::Submitted(tx_id, result.attempts),
+        None => ChunkOutcome::Failed(
+            vec![Some(Arc::new(Error::MalformedResponse("no transaction id"))); len],
+            result.attempts,
+        ),
+    }
+}
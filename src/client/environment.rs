@@ -2,7 +2,7 @@ use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
 /// An Agora Environment.
-#[derive(FromPrimitive)]
+#[derive(Debug, PartialEq, Eq, FromPrimitive, Copy, Clone)]
 pub enum Environment {
     /// Kin production blockchain.
     Production = 1,
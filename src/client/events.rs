@@ -0,0 +1,186 @@
+use {
+    crate::{
+        client::internal::account::InternalAccountClient,
+        error::{Error, TransactionError},
+        gen::kin::agora::account::v4 as account_pb,
+        key::public::PublicKey,
+        model::{event::Event, transaction_id::TransactionId},
+        retry::{
+            backoff::{BackoffStrategy, BinaryExponential},
+            is_transient,
+        },
+    },
+    futures::stream::{self, Stream},
+    std::{
+        collections::{HashSet, VecDeque},
+        pin::Pin,
+        time::Duration,
+    },
+    tonic::Streaming,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A stream of events for an account.
+pub type EventStream = Pin<Box<dyn Stream<Item = Result<Event>> + Send>>;
+
+/// The number of transaction ids remembered to avoid emitting a transaction twice after reconnecting.
+const SEEN_TRANSACTIONS_LEN: usize = 1024;
+
+/// The base delay between consecutive reconnects that do not receive any events.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// The maximum delay between reconnects.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Returns a stream of the events of an account.
+///
+/// If the connection to Agora is lost or closed, the stream reconnects and resumes, skipping any
+/// transactions that were already emitted. Batches of events that cannot be decoded are returned as
+/// errors, after which the stream continues. The stream ends after returning an error that cannot be
+/// recovered from, such as the account not existing.
+pub fn watch_account(client: InternalAccountClient, account: &PublicKey) -> EventStream {
+    let watcher = Watcher {
+        client,
+        account: *account,
+        stream: None,
+        pending: VecDeque::new(),
+        seen: HashSet::new(),
+        seen_order: VecDeque::new(),
+        reconnects: 0,
+        done: false,
+    };
+
+    Box::pin(stream::unfold(watcher, |mut watcher| async move {
+        watcher.next().await.map(|item| (item, watcher))
+    }))
+}
+
+struct Watcher {
+    client: InternalAccountClient,
+    account: PublicKey,
+    stream: Option<Streaming<account_pb::Events>>,
+
+    /// Events received but not yet emitted.
+    pending: VecDeque<Event>,
+
+    /// The ids of the most recently emitted transactions.
+    seen: HashSet<TransactionId>,
+    seen_order: VecDeque<TransactionId>,
+
+    /// The number of times the stream was reopened since the last events were received.
+    reconnects: u32,
+    done: bool,
+}
+
+impl Watcher {
+    async fn next(&mut self) -> Option<Result<Event>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+
+            if self.stream.is_none() {
+                // The first reconnect is immediate, as the server may have simply closed the stream.
+                if self.reconnects > 1 {
+                    let backoff = BinaryExponential {
+                        base: RECONNECT_BASE_DELAY,
+                    };
+                    let delay = backoff.get_backoff(self.reconnects - 1);
+                    tokio::time::sleep(delay.min(MAX_RECONNECT_DELAY)).await;
+                }
+
+                match self.client.get_events(&self.account).await {
+                    Ok(stream) => self.stream = Some(stream),
+                    Err(e) if is_transient(&e) => {
+                        self.reconnect();
+                        continue;
+                    }
+                    Err(e) => return Some(self.fail(e)),
+                }
+            }
+
+            let stream = self.stream.as_mut().unwrap();
+            match stream.message().await {
+                Ok(Some(events)) => {
+                    self.reconnects = 0;
+                    if events.result == account_pb::events::Result::NotFound as i32 {
+                        return Some(self.fail(TransactionError::AccountDoesNotExist(None).into()));
+                    }
+
+                    // A batch that cannot be decoded is skipped, without closing the stream.
+                    if let Err(e) = self.receive(events) {
+                        return Some(Err(e));
+                    }
+                }
+                Ok(None) => self.reconnect(),
+                Err(status) => {
+                    let error = Error::from(status);
+                    if !is_transient(&error) {
+                        return Some(self.fail(error));
+                    }
+
+                    self.reconnect();
+                }
+            }
+        }
+    }
+
+    /// Queues the events of a batch that were not already emitted. If any event in the batch cannot
+    /// be decoded, none of them are queued.
+    fn receive(&mut self, events: account_pb::Events) -> Result<()> {
+        if events.result != account_pb::events::Result::Ok as i32 {
+            return Err(Error::UnexpectedResult(events.result));
+        }
+
+        let mut decoded = Vec::new();
+        for event in &events.events {
+            if let Some(event) = Event::from_proto(event)? {
+                decoded.push(event);
+            }
+        }
+
+        for event in decoded {
+            if let Event::Transaction(data) = &event {
+                if !self.mark_seen(&data.tx_id) {
+                    continue;
+                }
+            }
+
+            self.pending.push_back(event);
+        }
+
+        Ok(())
+    }
+
+    /// Remembers a transaction id, returning false if it was already seen.
+    fn mark_seen(&mut self, tx_id: &TransactionId) -> bool {
+        if !self.seen.insert(tx_id.clone()) {
+            return false;
+        }
+
+        self.seen_order.push_back(tx_id.clone());
+        if self.seen_order.len() > SEEN_TRANSACTIONS_LEN {
+            if let Some(oldest) = self.seen_order.pop_front() {
+                self.seen.remove(&oldest);
+            }
+        }
+
+        true
+    }
+
+    fn reconnect(&mut self) {
+        self.stream = None;
+        self.reconnects += 1;
+    }
+
+    fn fail(&mut self, error: Error) -> Result<Event> {
+        self.stream = None;
+        self.done = true;
+
+        Err(error)
+    }
+}
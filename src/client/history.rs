@@ -0,0 +1,120 @@
+use {
+    crate::{
+        client::internal::transaction::InternalTransactionClient, error::Error,
+        gen::kin::agora::transaction::v4 as tx_pb, key::public::PublicKey,
+        model::transaction::TransactionData,
+    },
+    futures::stream::{self, Stream, StreamExt},
+    std::pin::Pin,
+};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A stream of the transactions in the history of an account.
+pub type HistoryStream = Pin<Box<dyn Stream<Item = Result<TransactionData>> + Send>>;
+
+/// The order in which the transaction history of an account is returned.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Direction {
+    /// Oldest transactions first.
+    Ascending,
+
+    /// Newest transactions first.
+    Descending,
+}
+
+impl Default for Direction {
+    fn default() -> Self {
+        Direction::Ascending
+    }
+}
+
+impl Direction {
+    pub fn to_proto(self) -> tx_pb::get_history_request::Direction {
+        match self {
+            Direction::Ascending => tx_pb::get_history_request::Direction::Asc,
+            Direction::Descending => tx_pb::get_history_request::Direction::Desc,
+        }
+    }
+}
+
+/// A page of the transaction history of an account.
+#[derive(Debug)]
+pub struct HistoryPage {
+    pub transactions: Vec<TransactionData>,
+
+    /// The cursor to retrieve the page following this one with, or None if this page is empty.
+    pub next_cursor: Option<Vec<u8>>,
+}
+
+/// Returns the page of the transaction history of an account that follows the cursor. Without a
+/// cursor, the first page is returned.
+pub async fn get_history(
+    client: &InternalTransactionClient,
+    account: &PublicKey,
+    cursor: Option<&[u8]>,
+    direction: Direction,
+) -> Result<HistoryPage> {
+    use tx_pb::get_transaction_response::State;
+
+    let items = client
+        .get_history(account, cursor, direction.to_proto())
+        .await?;
+
+    let next_cursor = match items.last() {
+        Some(item) => match &item.cursor {
+            Some(cursor) => Some(cursor.value.clone()),
+            None => return Err(Error::MalformedResponse("history item has no cursor")),
+        },
+        None => None,
+    };
+
+    let mut transactions = Vec::new();
+    for item in &items {
+        let state = match item.transaction_error {
+            Some(_) => State::Failed,
+            None => State::Success,
+        };
+        transactions.push(TransactionData::from_proto(item, state)?);
+    }
+
+    Ok(HistoryPage {
+        transactions,
+        next_cursor,
+    })
+}
+
+/// Returns a stream of the whole transaction history of an account, retrieving pages as needed.
+///
+/// The stream ends after the last page, or after the first error.
+pub fn history_stream(
+    client: InternalTransactionClient,
+    account: &PublicKey,
+    direction: Direction,
+) -> HistoryStream {
+    let account = *account;
+
+    // The state is the cursor of the next page, or None once there are no more pages.
+    let pages = stream::unfold(Some(None), move |cursor: Option<Option<Vec<u8>>>| {
+        let client = client.clone();
+        async move {
+            let cursor = cursor?;
+            match get_history(&client, &account, cursor.as_deref(), direction).await {
+                Ok(page) if page.transactions.is_empty() => None,
+                Ok(page) => {
+                    let next = Some(page.next_cursor);
+                    Some((Ok(page.transactions), next))
+                }
+                Err(e) => Some((Err(e), None)),
+            }
+        }
+    });
+
+    Box::pin(pages.flat_map(|page| {
+        let items: Vec<Result<TransactionData>> = match page {
+            Ok(transactions) => transactions.into_iter().map(Ok).collect(),
+            Err(e) => vec![Err(e)],
+        };
+        stream::iter(items)
+    }))
+}
@@ -1,12 +1,19 @@
 use {
     crate::{
-        client::{get_signers_and_funder, kin_memo_instruction, partial_sign, proto_tx},
+        client::{
+            get_mint_from_config, get_signers_and_funder, internal::AgoraChannel,
+            kin_memo_instruction, partial_sign, proto_tx,
+        },
         error::{Error, TransactionError},
         gen::kin::agora::{
             account::v4 as account_pb, common::v4 as model_pb_v4, transaction::v4 as tx_pb,
         },
         key::{private::PrivateKey, public::PublicKey},
-        model::transaction_type::TransactionType,
+        model::{
+            account::{public_key_from_proto, AccountInfo},
+            transaction_type::TransactionType,
+        },
+        retry::Retrier,
         solana::{
             commitment::Commitment, token::instruction::create_assoc_account_and_set_close_auth,
         },
@@ -16,30 +23,25 @@ use {
         pubkey::Pubkey as SolanaPublicKey, transaction::Transaction as SolanaTransaction,
     },
     spl_associated_token_account::get_associated_token_address,
-    tonic::transport::{Channel, ClientTlsConfig, Error as TonicError},
+    tonic::Streaming,
 };
 
+#[derive(Clone)]
 pub struct InternalAccountClient {
-    client: account_pb::account_client::AccountClient<Channel>,
+    client: account_pb::account_client::AccountClient<AgoraChannel>,
+    pub retrier: Retrier,
 }
 
 impl InternalAccountClient {
-    pub async fn new(endpoint: &'static str) -> Result<InternalAccountClient, TonicError> {
-        let tls = ClientTlsConfig::new();
-        let channel = Channel::from_static(endpoint)
-            .tls_config(tls)?
-            .connect()
-            .await?;
-
-        let account_client = InternalAccountClient {
+    pub fn new(channel: AgoraChannel, retrier: Retrier) -> InternalAccountClient {
+        InternalAccountClient {
             client: account_pb::account_client::AccountClient::new(channel),
-        };
-
-        Ok(account_client)
+            retrier,
+        }
     }
 
     pub async fn create_account(
-        &mut self,
+        &self,
         key: &PrivateKey,
         commitment: Commitment,
         app_index: u16,
@@ -50,7 +52,7 @@ impl InternalAccountClient {
         use account_pb::create_account_response::Result;
 
         let (signers, funder) = get_signers_and_funder(key, subsidizer, config)?;
-        let instructions = Self::get_create_account_instructions(key, app_index, &funder, config);
+        let instructions = Self::get_create_account_instructions(key, app_index, &funder, config)?;
 
         let mut tx = SolanaTransaction::new_with_payer(&instructions, Some(&funder));
         partial_sign(&mut tx, &signers, recent_blockhash);
@@ -60,22 +62,42 @@ impl InternalAccountClient {
             commitment: commitment as i32,
         };
 
-        let res = self.client.create_account(req).await.unwrap().into_inner();
+        // A retried request may fail with `Exists` if a previous attempt was processed by Agora
+        // despite failing on our end, so `Exists` after a retry is checked against the account.
+        let mut attempts = 0;
+        let res = self
+            .retrier
+            .retry(|| {
+                attempts += 1;
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.create_account(req).await }
+            })
+            .await?
+            .into_inner();
+
         match Result::from_i32(res.result) {
             Some(Result::Ok) => Ok(()),
+            Some(Result::Exists) if attempts > 1 => {
+                if self.is_created_by(key, &funder, config, commitment).await? {
+                    Ok(())
+                } else {
+                    Err(Error::AccountExists)
+                }
+            }
             Some(Result::Exists) => Err(Error::AccountExists),
             Some(Result::PayerRequired) => Err(Error::PayerRequired),
             Some(Result::BadNonce) => Err(TransactionError::BadNonce(None).into()),
-            None => panic!("Unexpected result from Agora: {}.", res.result),
+            None => Err(Error::UnexpectedResult(res.result)),
         }
     }
 
     #[allow(deprecated)]
     pub async fn resolve_token_accounts(
-        &mut self,
+        &self,
         public_key: &PublicKey,
         include_account_info: bool,
-    ) -> Vec<account_pb::AccountInfo> {
+    ) -> Result<Vec<AccountInfo>, Error> {
         let id = model_pb_v4::SolanaAccountId {
             value: public_key.to_bytes().to_vec(),
         };
@@ -86,10 +108,13 @@ impl InternalAccountClient {
         };
 
         let res = self
-            .client
-            .resolve_token_accounts(req)
-            .await
-            .unwrap()
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.resolve_token_accounts(req).await }
+            })
+            .await?
             .into_inner();
 
         let token_accounts = res.token_accounts;
@@ -99,20 +124,25 @@ impl InternalAccountClient {
         if !token_accounts.is_empty() && infos.len() != token_accounts.len() {
             // If we aren't requesting account info, we can interpolate the results ourselves.
             if !include_account_info {
-                return infos.iter().map(Self::to_info_with_id_only).collect();
+                return token_accounts
+                    .iter()
+                    .map(|id| Ok(AccountInfo::with_id_only(public_key_from_proto(id)?)))
+                    .collect();
             } else {
-                panic!("Server does not support resolving with account info.")
+                return Err(Error::MalformedResponse(
+                    "server does not support resolving with account info",
+                ));
             }
         }
 
-        infos
+        infos.iter().map(AccountInfo::from_proto).collect()
     }
 
     pub async fn get_account_info(
-        &mut self,
+        &self,
         public_key: &PublicKey,
         commitment: Commitment,
-    ) -> Result<account_pb::AccountInfo, TransactionError> {
+    ) -> Result<AccountInfo, Error> {
         use account_pb::get_account_info_response::Result;
 
         let account_id = model_pb_v4::SolanaAccountId {
@@ -125,26 +155,78 @@ impl InternalAccountClient {
         };
 
         let res = self
-            .client
-            .get_account_info(req)
-            .await
-            .unwrap()
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_account_info(req).await }
+            })
+            .await?
             .into_inner();
 
         match Result::from_i32(res.result) {
-            Some(Result::Ok) => Ok(res.account_info.unwrap()),
-            Some(Result::NotFound) => Err(TransactionError::AccountDoesNotExist(None)),
-            None => panic!("Unexpected result from Agora: {}.", res.result),
+            Some(Result::Ok) => match &res.account_info {
+                Some(info) => AccountInfo::from_proto(info),
+                None => Err(Error::MalformedResponse("no account info received")),
+            },
+            Some(Result::NotFound) => Err(TransactionError::AccountDoesNotExist(None).into()),
+            None => Err(Error::UnexpectedResult(res.result)),
         }
     }
 
-    fn to_info_with_id_only(info: &account_pb::AccountInfo) -> account_pb::AccountInfo {
-        account_pb::AccountInfo {
-            account_id: info.account_id.clone(),
-            balance: 0,
-            owner: None,
-            close_authority: None,
-        }
+    /// Opens a stream of events for an account.
+    pub async fn get_events(
+        &self,
+        public_key: &PublicKey,
+    ) -> Result<Streaming<account_pb::Events>, Error> {
+        let account_id = model_pb_v4::SolanaAccountId {
+            value: public_key.to_bytes().to_vec(),
+        };
+
+        let req = account_pb::GetEventsRequest {
+            account_id: Some(account_id),
+        };
+
+        let stream = self
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_events(req).await }
+            })
+            .await?
+            .into_inner();
+
+        Ok(stream)
+    }
+
+    /// Returns whether the associated token account of `key` is one that a create account
+    /// transaction paid for by `funder` would have created, that is, whether it is owned by `key` and
+    /// can be closed by `funder`.
+    async fn is_created_by(
+        &self,
+        key: &PrivateKey,
+        funder: &SolanaPublicKey,
+        config: &tx_pb::GetServiceConfigResponse,
+        commitment: Commitment,
+    ) -> Result<bool, Error> {
+        let owner = key.public_key();
+        let mint = get_mint_from_config(config)?;
+        let assoc = get_associated_token_address(&owner.to_solana_key(), &mint);
+
+        let info = match self
+            .get_account_info(&PublicKey::new(assoc.as_ref()), commitment)
+            .await
+        {
+            Ok(info) => info,
+            Err(Error::TransactionFailed(TransactionError::AccountDoesNotExist(_))) => {
+                return Ok(false)
+            }
+            Err(e) => return Err(e),
+        };
+
+        Ok(info.owner == Some(owner)
+            && info.close_authority == Some(PublicKey::new(funder.as_ref())))
     }
 
     fn get_create_account_instructions(
@@ -152,7 +234,7 @@ impl InternalAccountClient {
         app_index: u16,
         funder: &SolanaPublicKey,
         config: &tx_pb::GetServiceConfigResponse,
-    ) -> Vec<Instruction> {
+    ) -> Result<Vec<Instruction>, Error> {
         let mut instructions = Vec::new();
 
         if app_index > 0 {
@@ -164,12 +246,12 @@ impl InternalAccountClient {
         }
 
         let owner = key.public_key().to_solana_key();
-        let mint = SolanaPublicKey::new(&config.token.as_ref().unwrap().value);
+        let mint = get_mint_from_config(config)?;
         let assoc = get_associated_token_address(&owner, &mint);
         instructions.append(&mut create_assoc_account_and_set_close_auth(
             funder, &owner, &mint, &assoc,
         ));
 
-        instructions
+        Ok(instructions)
     }
 }
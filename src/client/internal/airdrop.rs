@@ -1,38 +1,34 @@
-use {
-    crate::{
-        error::{Error, TransactionError},
-        gen::kin::agora::{airdrop::v4 as airdrop_pb, common::v4 as model_pb_v4},
-        key::public::PublicKey,
-        solana::commitment::Commitment,
-    },
-    tonic::transport::{Channel, ClientTlsConfig, Error as TonicError},
+use crate::{
+    client::internal::AgoraChannel,
+    error::{Error, TransactionError},
+    gen::kin::agora::{airdrop::v4 as airdrop_pb, common::v4 as model_pb_v4},
+    key::public::PublicKey,
+    model::transaction_id::TransactionId,
+    solana::commitment::Commitment,
 };
 
+#[derive(Clone)]
 pub struct InternalAirdropClient {
-    client: airdrop_pb::airdrop_client::AirdropClient<Channel>,
+    client: airdrop_pb::airdrop_client::AirdropClient<AgoraChannel>,
 }
 
 impl InternalAirdropClient {
-    pub async fn new(endpoint: &'static str) -> Result<InternalAirdropClient, TonicError> {
-        let tls = ClientTlsConfig::new();
-        let channel = Channel::from_static(endpoint)
-            .tls_config(tls)?
-            .connect()
-            .await?;
-
-        let airdrop_client = InternalAirdropClient {
+    pub fn new(channel: AgoraChannel) -> InternalAirdropClient {
+        InternalAirdropClient {
             client: airdrop_pb::airdrop_client::AirdropClient::new(channel),
-        };
-
-        Ok(airdrop_client)
+        }
     }
 
+    /// Requests an airdrop of Kin to a token account.
+    ///
+    /// The request is not retried: airdrops are not idempotent, and the airdrop transaction is
+    /// built by Agora, so there is no signature to check whether a failed attempt was applied.
     pub async fn request_airdrop(
-        &mut self,
+        &self,
         public_key: &PublicKey,
         quarks: u64,
         commitment: Commitment,
-    ) -> Result<Vec<u8>, Error> {
+    ) -> Result<TransactionId, Error> {
         let account_id = model_pb_v4::SolanaAccountId {
             value: public_key.to_bytes().to_vec(),
         };
@@ -43,19 +39,19 @@ impl InternalAirdropClient {
             commitment: commitment as i32,
         };
 
-        let res = self.client.request_airdrop(req).await.unwrap().into_inner();
+        let res = self.client.clone().request_airdrop(req).await?.into_inner();
 
         use airdrop_pb::request_airdrop_response::Result;
         match Result::from_i32(res.result) {
             Some(Result::Ok) => match res.signature {
-                Some(sig) => Ok(sig.value),
-                None => panic!("No signature received from Agora."),
+                Some(sig) => Ok(TransactionId::from(sig.value)),
+                None => Err(Error::MalformedResponse("no signature received")),
             },
             Some(Result::NotFound) => Err(TransactionError::AccountDoesNotExist(None).into()),
             Some(Result::InsufficientKin) => {
                 Err(TransactionError::InsufficientBalance(None).into())
             }
-            None => panic!("Unexpected result from Agora: {}.", res.result),
+            None => Err(Error::UnexpectedResult(res.result)),
         }
     }
 }
@@ -0,0 +1,104 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The default amount of time the service config is cached for.
+pub const DEFAULT_SERVICE_CONFIG_TTL: Duration = Duration::from_secs(30 * 60);
+
+/// The default amount of time a recent blockhash is cached for.
+///
+/// This is kept short, as transactions with identical instructions and blockhashes also have
+/// identical signatures, and would be rejected as already submitted.
+pub const DEFAULT_BLOCKHASH_TTL: Duration = Duration::from_secs(1);
+
+/// Determines how long responses from Agora are cached for. A TTL of zero disables caching.
+#[derive(Debug, Copy, Clone)]
+pub struct CacheConfig {
+    pub service_config_ttl: Duration,
+    pub blockhash_ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            service_config_ttl: DEFAULT_SERVICE_CONFIG_TTL,
+            blockhash_ttl: DEFAULT_BLOCKHASH_TTL,
+        }
+    }
+}
+
+/// Holds a single value that expires after a fixed amount of time.
+///
+/// Clones share the same value.
+#[derive(Clone)]
+pub struct Cache<T: Clone> {
+    ttl: Duration,
+    entry: Arc<Mutex<Option<(Instant, T)>>>,
+}
+
+impl<T: Clone> Cache<T> {
+    pub fn new(ttl: Duration) -> Cache<T> {
+        Cache {
+            ttl,
+            entry: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns the cached value, or None if there is no value or it has expired.
+    pub fn get(&self) -> Option<T> {
+        let entry = self.entry.lock().unwrap();
+
+        match &*entry {
+            Some((set_at, value)) if set_at.elapsed() < self.ttl => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn set(&self, value: T) {
+        *self.entry.lock().unwrap() = Some((Instant::now(), value));
+    }
+
+    pub fn invalidate(&self) {
+        *self.entry.lock().unwrap() = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_and_invalidate() {
+        let cache = Cache::new(Duration::from_secs(60));
+        assert_eq!(cache.get(), None);
+
+        cache.set(1);
+        assert_eq!(cache.get(), Some(1));
+
+        // Clones share the same value.
+        let clone = cache.clone();
+        clone.set(2);
+        assert_eq!(cache.get(), Some(2));
+
+        cache.invalidate();
+        assert_eq!(clone.get(), None);
+    }
+
+    #[test]
+    fn expires() {
+        let cache = Cache::new(Duration::from_millis(10));
+        cache.set(1);
+
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get(), None);
+    }
+
+    #[test]
+    fn zero_ttl_disables_caching() {
+        let cache = Cache::new(Duration::from_secs(0));
+        cache.set(1);
+
+        assert_eq!(cache.get(), None);
+    }
+}
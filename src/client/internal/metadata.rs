@@ -0,0 +1,123 @@
+use {
+    crate::error::Error,
+    std::sync::Arc,
+    tonic::{
+        metadata::{AsciiMetadataKey, AsciiMetadataValue, MetadataMap},
+        service::Interceptor,
+        Request, Status,
+    },
+};
+
+pub const USER_AGENT_HEADER: &str = "kin-user-agent";
+pub const KIN_VERSION_HEADER: &str = "kin-version";
+pub const APP_INDEX_HEADER: &str = "app-index";
+
+/// The version of Kin supported by this SDK.
+const KIN_VERSION: &str = "4";
+
+/// A function that is called with the metadata of every request to Agora, and which may modify it.
+pub type MetadataHook = Arc<dyn Fn(&mut MetadataMap) + Send + Sync>;
+
+/// Adds the metadata expected by Agora to every request, as well as any headers set by the user.
+#[derive(Clone)]
+pub struct MetadataInterceptor {
+    headers: Arc<Vec<(AsciiMetadataKey, AsciiMetadataValue)>>,
+    hook: Option<MetadataHook>,
+}
+
+impl MetadataInterceptor {
+    /// Returns an interceptor that adds the SDK headers, followed by the provided headers and hook.
+    pub fn new(
+        app_index: u16,
+        headers: &[(String, String)],
+        hook: Option<MetadataHook>,
+    ) -> Result<MetadataInterceptor, Error> {
+        let user_agent = format!("KinSDK/{} rust", env!("CARGO_PKG_VERSION"));
+
+        let mut sdk_headers = vec![
+            (USER_AGENT_HEADER.to_string(), user_agent),
+            (KIN_VERSION_HEADER.to_string(), KIN_VERSION.to_string()),
+        ];
+        if app_index > 0 {
+            sdk_headers.push((APP_INDEX_HEADER.to_string(), app_index.to_string()));
+        }
+
+        let mut parsed = Vec::new();
+        for (key, value) in sdk_headers.iter().chain(headers) {
+            let header_key = AsciiMetadataKey::from_bytes(key.as_bytes())
+                .map_err(|_| Error::InvalidHeader(key.clone()))?;
+            let header_value = AsciiMetadataValue::from_str(value)
+                .map_err(|_| Error::InvalidHeader(key.clone()))?;
+
+            parsed.push((header_key, header_value));
+        }
+
+        Ok(MetadataInterceptor {
+            headers: Arc::new(parsed),
+            hook,
+        })
+    }
+}
+
+impl Interceptor for MetadataInterceptor {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let metadata = request.metadata_mut();
+        for (key, value) in self.headers.iter() {
+            metadata.insert(key.clone(), value.clone());
+        }
+
+        if let Some(hook) = &self.hook {
+            hook(metadata);
+        }
+
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_sdk_headers() {
+        let mut interceptor = MetadataInterceptor::new(2, &[], None).unwrap();
+        let request = interceptor.call(Request::new(())).unwrap();
+        let metadata = request.metadata();
+
+        let user_agent = metadata.get(USER_AGENT_HEADER).unwrap().to_str().unwrap();
+        assert!(user_agent.starts_with("KinSDK/"));
+        assert_eq!(metadata.get(KIN_VERSION_HEADER).unwrap(), "4");
+        assert_eq!(metadata.get(APP_INDEX_HEADER).unwrap(), "2");
+    }
+
+    #[test]
+    fn omits_app_index_when_not_set() {
+        let mut interceptor = MetadataInterceptor::new(0, &[], None).unwrap();
+        let request = interceptor.call(Request::new(())).unwrap();
+
+        assert!(request.metadata().get(APP_INDEX_HEADER).is_none());
+    }
+
+    #[test]
+    fn adds_user_headers_and_hook() {
+        let headers = [("x-request-source".to_string(), "payouts".to_string())];
+        let hook: MetadataHook = Arc::new(|metadata| {
+            metadata.insert("x-hook", "called".parse().unwrap());
+        });
+
+        let mut interceptor = MetadataInterceptor::new(0, &headers, Some(hook)).unwrap();
+        let request = interceptor.call(Request::new(())).unwrap();
+        let metadata = request.metadata();
+
+        assert_eq!(metadata.get("x-request-source").unwrap(), "payouts");
+        assert_eq!(metadata.get("x-hook").unwrap(), "called");
+    }
+
+    #[test]
+    fn invalid_header() {
+        let headers = [("invalid key".to_string(), "value".to_string())];
+        let result = MetadataInterceptor::new(0, &headers, None);
+
+        assert!(matches!(result, Err(Error::InvalidHeader(_))));
+    }
+}
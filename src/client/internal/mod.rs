@@ -1,13 +1,51 @@
 pub mod account;
 pub mod airdrop;
+pub mod cache;
+pub mod metadata;
 pub mod transaction;
 
-use account::InternalAccountClient;
-use airdrop::InternalAirdropClient;
-use transaction::InternalTransactionClient;
+use {
+    crate::retry::Retrier,
+    account::InternalAccountClient,
+    airdrop::InternalAirdropClient,
+    cache::CacheConfig,
+    metadata::MetadataInterceptor,
+    tonic::{codegen::InterceptedService, transport::Channel},
+    transaction::InternalTransactionClient,
+};
 
+/// A channel to Agora that adds the SDK metadata to every request.
+pub type AgoraChannel = InterceptedService<Channel, MetadataInterceptor>;
+
+#[derive(Clone)]
 pub struct InternalClient {
     pub account: InternalAccountClient,
     pub tx: InternalTransactionClient,
     pub airdrop: InternalAirdropClient,
 }
+
+impl InternalClient {
+    /// Returns an internal client whose services share a single channel.
+    ///
+    /// Channels are cheap to clone, and all clones use the same underlying connection.
+    pub fn new(
+        channel: Channel,
+        interceptor: MetadataInterceptor,
+        retrier: Retrier,
+        cache_config: CacheConfig,
+    ) -> InternalClient {
+        let channel = InterceptedService::new(channel, interceptor);
+
+        InternalClient {
+            account: InternalAccountClient::new(channel.clone(), retrier.clone()),
+            tx: InternalTransactionClient::new(channel.clone(), retrier, cache_config),
+            airdrop: InternalAirdropClient::new(channel),
+        }
+    }
+
+    /// Sets the retrier used for all calls to Agora, except for airdrops, which are never retried.
+    pub fn set_retrier(&mut self, retrier: Retrier) {
+        self.account.retrier = retrier.clone();
+        self.tx.retrier = retrier;
+    }
+}
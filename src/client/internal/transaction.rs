@@ -1,101 +1,172 @@
 use {
     crate::{
-        client::proto_tx,
-        error::{Error, TransactionError::AlreadySubmitted, TransactionErrors},
+        client::{
+            internal::{
+                cache::{Cache, CacheConfig},
+                AgoraChannel,
+            },
+            proto_tx,
+        },
+        error::{
+            Error,
+            TransactionError::{self, AlreadySubmitted},
+            TransactionErrors,
+        },
         gen::kin::agora::{
             common::{v3 as model_pb_v3, v4 as model_pb_v4},
             transaction::v4 as tx_pb,
         },
-        model::transaction::{TransactionData, TransactionState},
+        key::public::PublicKey,
+        model::{
+            transaction::{TransactionData, TransactionState},
+            transaction_id::TransactionId,
+        },
+        retry::Retrier,
         solana::{commitment::Commitment, token::program::ACCOUNT_LEN},
     },
-    solana_sdk::transaction::Transaction as SolanaTransaction,
-    tonic::transport::{Channel, ClientTlsConfig, Error as TonicError},
+    solana_sdk::{hash::HASH_BYTES, transaction::Transaction as SolanaTransaction},
 };
 
 #[derive(Debug)]
 pub struct SignTransactionResult {
-    pub transaction_id: Option<Vec<u8>>,
+    pub transaction_id: Option<TransactionId>,
     pub invoice_errors: Option<Vec<model_pb_v3::InvoiceError>>,
 }
 
 #[derive(Debug)]
 pub struct SubmitTransactionResult {
-    pub tx_id: Option<Vec<u8>>,
+    pub tx_id: Option<TransactionId>,
     pub invoice_errors: Option<Vec<model_pb_v3::InvoiceError>>,
     pub errors: Option<TransactionErrors>,
+
+    /// The number of times the transaction was signed and submitted, including retries due to a bad
+    /// nonce.
+    pub attempts: u32,
 }
 
+#[derive(Clone)]
 pub struct InternalTransactionClient {
-    client: tx_pb::transaction_client::TransactionClient<Channel>,
+    client: tx_pb::transaction_client::TransactionClient<AgoraChannel>,
+    pub retrier: Retrier,
+    service_config: Cache<tx_pb::GetServiceConfigResponse>,
+    blockhash: Cache<Vec<u8>>,
 }
 
 impl InternalTransactionClient {
-    pub async fn new(endpoint: &'static str) -> Result<InternalTransactionClient, TonicError> {
-        let tls = ClientTlsConfig::new();
-        let channel = Channel::from_static(endpoint)
-            .tls_config(tls)?
-            .connect()
-            .await?;
-
-        let tx_client = InternalTransactionClient {
+    pub fn new(
+        channel: AgoraChannel,
+        retrier: Retrier,
+        cache_config: CacheConfig,
+    ) -> InternalTransactionClient {
+        InternalTransactionClient {
             client: tx_pb::transaction_client::TransactionClient::new(channel),
-        };
+            retrier,
+            service_config: Cache::new(cache_config.service_config_ttl),
+            blockhash: Cache::new(cache_config.blockhash_ttl),
+        }
+    }
+
+    /// Returns the service config, which is cached.
+    pub async fn get_service_config(&self) -> Result<tx_pb::GetServiceConfigResponse, Error> {
+        if let Some(config) = self.service_config.get() {
+            return Ok(config);
+        }
 
-        Ok(tx_client)
+        self.refresh_service_config().await
     }
 
-    pub async fn get_service_config(&mut self) -> tx_pb::GetServiceConfigResponse {
+    /// Retrieves the service config from Agora, and replaces the cached config with it.
+    pub async fn refresh_service_config(&self) -> Result<tx_pb::GetServiceConfigResponse, Error> {
         let req = tx_pb::GetServiceConfigRequest {};
 
-        self.client
-            .get_service_config(req)
-            .await
-            .unwrap()
-            .into_inner()
+        let res = self
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_service_config(req).await }
+            })
+            .await?
+            .into_inner();
+
+        self.service_config.set(res.clone());
+
+        Ok(res)
     }
 
-    pub async fn get_recent_blockhash(&mut self) -> Vec<u8> {
+    /// Returns a recent blockhash, which is briefly cached.
+    pub async fn get_recent_blockhash(&self) -> Result<Vec<u8>, Error> {
+        if let Some(blockhash) = self.blockhash.get() {
+            return Ok(blockhash);
+        }
+
         let req = tx_pb::GetRecentBlockhashRequest {};
 
         let res = self
-            .client
-            .get_recent_blockhash(req)
-            .await
-            .unwrap()
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_recent_blockhash(req).await }
+            })
+            .await?
             .into_inner();
 
-        res.blockhash.unwrap().value
+        match res.blockhash {
+            Some(blockhash) if blockhash.value.len() == HASH_BYTES => {
+                self.blockhash.set(blockhash.value.clone());
+                Ok(blockhash.value)
+            }
+            Some(_) => Err(Error::MalformedResponse("invalid blockhash")),
+            None => Err(Error::MalformedResponse("no blockhash received")),
+        }
+    }
+
+    /// Removes the cached blockhash, so that the next call to `get_recent_blockhash` retrieves a new
+    /// one. This should be called when a transaction fails due to a bad nonce.
+    pub fn invalidate_blockhash(&self) {
+        self.blockhash.invalidate();
     }
 
-    pub async fn get_minimum_kin_version(&mut self) -> tx_pb::GetMinimumKinVersionResponse {
+    pub async fn get_minimum_kin_version(
+        &self,
+    ) -> Result<tx_pb::GetMinimumKinVersionResponse, Error> {
         let req = tx_pb::GetMinimumKinVersionRequest {};
 
-        self.client
-            .get_minimum_kin_version(req)
-            .await
-            .unwrap()
-            .into_inner()
+        let res = self
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_minimum_kin_version(req).await }
+            })
+            .await?
+            .into_inner();
+
+        Ok(res)
     }
 
-    pub async fn get_minimum_balance_for_rent_exemption(&mut self) -> u64 {
+    pub async fn get_minimum_balance_for_rent_exemption(&self) -> Result<u64, Error> {
         let req = tx_pb::GetMinimumBalanceForRentExemptionRequest { size: ACCOUNT_LEN };
 
         let res = self
-            .client
-            .get_minimum_balance_for_rent_exemption(req)
-            .await
-            .unwrap()
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_minimum_balance_for_rent_exemption(req).await }
+            })
+            .await?
             .into_inner();
 
-        res.lamports
+        Ok(res.lamports)
     }
 
     pub async fn get_transaction(
-        &mut self,
-        id: &[u8],
+        &self,
+        id: &TransactionId,
         commitment: Option<Commitment>,
-    ) -> TransactionData {
+    ) -> Result<TransactionData, Error> {
         let tx_id = model_pb_v4::TransactionId { value: id.to_vec() };
 
         let req = tx_pb::GetTransactionRequest {
@@ -103,41 +174,87 @@ impl InternalTransactionClient {
             commitment: commitment.unwrap_or_default() as i32,
         };
 
-        let res = self.client.get_transaction(req).await.unwrap().into_inner();
+        let res = self
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_transaction(req).await }
+            })
+            .await?
+            .into_inner();
 
         use tx_pb::get_transaction_response::State;
         let state = match State::from_i32(res.state) {
             Some(state) => state,
-            None => panic!("Unexpected state from Agora."),
+            None => return Err(Error::UnexpectedResult(res.state)),
         };
 
         match res.item {
             Some(item) => TransactionData::from_proto(&item, state),
-            None => TransactionData::new(id.to_vec(), TransactionState::from_proto(state)),
+            None => Ok(TransactionData::new(
+                id.clone(),
+                TransactionState::from_proto(state),
+            )),
+        }
+    }
+
+    /// Returns a page of the transaction history of an account, starting after the cursor.
+    pub async fn get_history(
+        &self,
+        public_key: &PublicKey,
+        cursor: Option<&[u8]>,
+        direction: tx_pb::get_history_request::Direction,
+    ) -> Result<Vec<tx_pb::HistoryItem>, Error> {
+        let req = tx_pb::GetHistoryRequest {
+            account_id: Some(model_pb_v4::SolanaAccountId {
+                value: public_key.to_bytes().to_vec(),
+            }),
+            cursor: cursor.map(|cursor| tx_pb::Cursor {
+                value: cursor.to_vec(),
+            }),
+            direction: direction as i32,
+        };
+
+        let res = self
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.get_history(req).await }
+            })
+            .await?
+            .into_inner();
+
+        use tx_pb::get_history_response::Result;
+        match Result::from_i32(res.result) {
+            Some(Result::Ok) => Ok(res.items),
+            Some(Result::NotFound) => Err(TransactionError::AccountDoesNotExist(None).into()),
+            None => Err(Error::UnexpectedResult(res.result)),
         }
     }
 
     pub async fn sign_transaction(
-        &mut self,
+        &self,
         transaction: &SolanaTransaction,
         invoice_list: Option<&model_pb_v3::InvoiceList>,
-    ) -> SignTransactionResult {
+    ) -> Result<SignTransactionResult, Error> {
         let req = tx_pb::SignTransactionRequest {
             transaction: Some(proto_tx(transaction)),
             invoice_list: invoice_list.cloned(),
         };
 
         let res = self
-            .client
-            .sign_transaction(req)
-            .await
-            .unwrap()
+            .retrier
+            .retry(|| {
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move { client.sign_transaction(req).await }
+            })
+            .await?
             .into_inner();
 
-        let transaction_id = match res.signature {
-            Some(sig) => Some(sig.value),
-            None => None,
-        };
+        let transaction_id = res.signature.map(|sig| TransactionId::from(sig.value));
 
         let mut sign_result = SignTransactionResult {
             transaction_id,
@@ -147,18 +264,18 @@ impl InternalTransactionClient {
         use tx_pb::sign_transaction_response::Result;
         match Result::from_i32(res.result) {
             Some(Result::Ok) => (),
-            Some(Result::Rejected) => panic!("Rejected."),
+            Some(Result::Rejected) => return Err(Error::TransactionRejected),
             Some(Result::InvoiceError) => {
                 sign_result.invoice_errors = Some(res.invoice_errors);
             }
-            None => panic!("Unexpected result from Agora: {}.", res.result),
+            None => return Err(Error::UnexpectedResult(res.result)),
         }
 
-        sign_result
+        Ok(sign_result)
     }
 
     pub async fn submit_transaction(
-        &mut self,
+        &self,
         tx: &SolanaTransaction,
         invoice_list: Option<&model_pb_v3::InvoiceList>,
         commitment: Option<Commitment>,
@@ -182,22 +299,37 @@ impl InternalTransactionClient {
             send_simulation_event: false,
         };
 
+        // Without a dedupe id, Agora could process a retried submission twice if a previous attempt
+        // was processed despite failing on our end. To avoid this, the transaction is looked up before
+        // each retry, and only resubmitted if Agora has not seen it.
+        let mut attempts = 0;
         let res = self
-            .client
-            .submit_transaction(req)
-            .await
-            .unwrap()
-            .into_inner();
+            .retrier
+            .retry(|| {
+                attempts += 1;
+                let check_previous = attempts > 1 && req.dedupe_id.is_empty();
+                let mut client = self.client.clone();
+                let req = req.clone();
+                async move {
+                    if check_previous {
+                        if let Some(res) = Self::get_previous_submission(&mut client, tx).await? {
+                            return Ok(res);
+                        }
+                    }
+
+                    let res = client.submit_transaction(req).await?;
+                    Ok::<_, Error>(res.into_inner())
+                }
+            })
+            .await?;
 
-        let tx_id = match res.signature {
-            Some(sig) => Some(sig.value),
-            None => None,
-        };
+        let tx_id = res.signature.map(|sig| TransactionId::from(sig.value));
 
         let mut submit_result = SubmitTransactionResult {
             tx_id: tx_id.clone(),
             invoice_errors: None,
             errors: None,
+            attempts: 1,
         };
 
         use tx_pb::submit_transaction_response::Result;
@@ -212,15 +344,46 @@ impl InternalTransactionClient {
                 submit_result.invoice_errors = Some(res.invoice_errors);
             }
             Some(Result::Failed) => {
-                submit_result.errors = Some(TransactionErrors::from_solana_tx(
-                    tx,
-                    &res.transaction_error.unwrap(),
-                    tx_id,
-                ));
+                let tx_error = res
+                    .transaction_error
+                    .ok_or(Error::MalformedResponse("no transaction error received"))?;
+
+                submit_result.errors =
+                    Some(TransactionErrors::from_solana_tx(tx, &tx_error, tx_id)?);
             }
-            None => panic!("Unexpected result from Agora: {}.", res.result),
+            None => return Err(Error::UnexpectedResult(res.result)),
         }
 
         Ok(submit_result)
     }
+
+    /// Returns the result of a previous submission of the transaction, or None if Agora has not seen it.
+    async fn get_previous_submission(
+        client: &mut tx_pb::transaction_client::TransactionClient<AgoraChannel>,
+        tx: &SolanaTransaction,
+    ) -> Result<Option<tx_pb::SubmitTransactionResponse>, Error> {
+        use tx_pb::{get_transaction_response::State, submit_transaction_response::Result};
+
+        let signature = tx.signatures[0].as_ref().to_vec();
+        let req = tx_pb::GetTransactionRequest {
+            transaction_id: Some(model_pb_v4::TransactionId {
+                value: signature.clone(),
+            }),
+            commitment: Commitment::Recent as i32,
+        };
+
+        let res = client.get_transaction(req).await?.into_inner();
+        let result = match State::from_i32(res.state) {
+            Some(State::Success) | Some(State::Pending) => Result::Ok,
+            Some(State::Failed) => Result::Failed,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(tx_pb::SubmitTransactionResponse {
+            result: result as i32,
+            signature: Some(model_pb_v4::TransactionSignature { value: signature }),
+            transaction_error: res.item.and_then(|item| item.transaction_error),
+            ..Default::default()
+        }))
+    }
 }
@@ -1,15 +1,21 @@
 pub mod account_resolution;
+pub mod builder;
 pub mod client;
+pub mod earn_batch;
 pub mod environment;
+pub mod events;
+pub mod history;
 pub mod internal;
 pub mod payment_submission;
 
 use {
     crate::{
         error::Error,
-        gen::kin::agora::common::v4 as model_pb_v4,
+        gen::kin::agora::common::{v3 as model_pb_v3, v4 as model_pb_v4},
         gen::kin::agora::transaction::v4 as tx_pb,
         key::private::PrivateKey,
+        model::account::public_key_from_proto,
+        model::invoice::{Invoice, InvoiceList},
         model::memo::Memo,
         model::transaction_type::TransactionType,
         solana::memo::program::{MemoParams, MemoProgram},
@@ -42,11 +48,25 @@ fn get_subsidizer_from_config(
     config: &tx_pb::GetServiceConfigResponse,
 ) -> Result<SolanaPublicKey, Error> {
     match &config.subsidizer_account {
-        Some(account) => Ok(SolanaPublicKey::new(&account.value)),
+        Some(account) => solana_key_from_proto(account),
         None => Err(Error::NoSubsidizer),
     }
 }
 
+fn get_mint_from_config(
+    config: &tx_pb::GetServiceConfigResponse,
+) -> Result<SolanaPublicKey, Error> {
+    match &config.token {
+        Some(token) => solana_key_from_proto(token),
+        None => Err(Error::MalformedResponse("service config has no token")),
+    }
+}
+
+/// Returns the Solana public key of an account id received from Agora.
+fn solana_key_from_proto(id: &model_pb_v4::SolanaAccountId) -> Result<SolanaPublicKey, Error> {
+    Ok(public_key_from_proto(id)?.to_solana_key())
+}
+
 fn partial_sign(tx: &mut SolanaTransaction, signers: &[&PrivateKey], recent_blockhash: &[u8]) {
     let keypairs: Vec<SolanaKeypair> = signers
         .iter()
@@ -68,6 +88,20 @@ fn kin_memo_instruction(
     })
 }
 
+/// Returns the memo foreign key of a transaction with one invoice per payment, along with the
+/// invoice list that must be submitted with it. Without invoices, the foreign key is all zeros.
+fn invoice_foreign_key(invoices: &[Invoice]) -> (Vec<u8>, Option<model_pb_v3::InvoiceList>) {
+    if invoices.is_empty() {
+        return ([0; 29].to_vec(), None);
+    }
+
+    let invoice_list = InvoiceList::new(invoices);
+    (
+        invoice_list.get_sha244_hash(),
+        Some(invoice_list.to_proto()),
+    )
+}
+
 fn proto_tx(tx: &SolanaTransaction) -> model_pb_v4::Transaction {
     model_pb_v4::Transaction {
         value: bincode::serialize(tx).unwrap(),
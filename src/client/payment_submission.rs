@@ -4,12 +4,20 @@ use {
             account_resolution::AccountResolution,
             client::Client,
             internal::transaction::SubmitTransactionResult,
-            {get_signers_and_funder, get_subsidizer_from_config, kin_memo_instruction},
+            {
+                get_mint_from_config, get_signers_and_funder, get_subsidizer_from_config,
+                invoice_foreign_key,
+            },
         },
         error::{Error, TransactionError},
         gen::kin::agora::{common::v3 as model_pb_v3, transaction::v4 as tx_pb},
         key::{private::PrivateKey, public::PublicKey},
-        model::{invoice::InvoiceList, payment::Payment},
+        model::{
+            invoice::Invoice,
+            memo::Memo,
+            payment::{Payment, PaymentReceipt},
+            transaction_id::TransactionId,
+        },
         solana::{
             commitment::Commitment,
             memo::program::{MemoParams, MemoProgram},
@@ -29,18 +37,18 @@ use {
 type Result<T> = std::result::Result<T, Error>;
 
 pub async fn submit_payment(
-    client: &mut Client,
+    client: &Client,
     payment: &mut Payment,
     commitment: Option<Commitment>,
     sender_resolution: Option<AccountResolution>,
     destination_resolution: Option<AccountResolution>,
     create_destination_token_account: bool,
-) -> Result<Option<Vec<u8>>> {
+) -> Result<PaymentReceipt> {
     if payment.invoice.is_some() && client.app_index == 0 {
-        panic!("App index required to use invoices.");
+        return Err(Error::AppIndexRequired);
     }
 
-    let result = submit_payment_with_resolution(
+    let (result, details) = submit_payment_with_resolution(
         client,
         payment,
         commitment,
@@ -50,14 +58,45 @@ pub async fn submit_payment(
     )
     .await?;
 
-    handle_submit_payment_result(result)
+    handle_submit_payment_result(result, details)
+}
+
+/// The details of a submitted payment, from which its receipt is built once the submission is known
+/// to have succeeded.
+struct PaymentDetails {
+    sender: PublicKey,
+    destination: PublicKey,
+    destination_created: bool,
+    fee_payer: PublicKey,
+    invoice_hash: Option<Vec<u8>>,
+    memo: Option<String>,
+}
+
+impl PaymentDetails {
+    fn into_receipt(self, tx_id: TransactionId, attempts: u32) -> PaymentReceipt {
+        PaymentReceipt {
+            tx_id,
+            sender: self.sender,
+            destination: self.destination,
+            destination_created: self.destination_created,
+            fee_payer: self.fee_payer,
+            invoice_hash: self.invoice_hash,
+            memo: self.memo,
+            attempts,
+        }
+    }
 }
 
-fn handle_submit_payment_result(result: SubmitTransactionResult) -> Result<Option<Vec<u8>>> {
+fn handle_submit_payment_result(
+    result: SubmitTransactionResult,
+    details: PaymentDetails,
+) -> Result<PaymentReceipt> {
     if let Some(errors) = result.errors {
         if let Some(payment_errors) = errors.payment_errors {
             if payment_errors.len() != 1 {
-                panic!("Invalid number of payment errors, expected 0 or 1. Found: {}. Payment errors: {:?}", payment_errors.len(), payment_errors);
+                return Err(Error::MalformedResponse(
+                    "invalid number of payment errors, expected 0 or 1",
+                ));
             }
             if let Some(e) = &payment_errors[0] {
                 return Err(e.clone().into());
@@ -70,36 +109,37 @@ fn handle_submit_payment_result(result: SubmitTransactionResult) -> Result<Optio
     }
 
     if let Some(invoice_errors) = result.invoice_errors {
-        if !invoice_errors.is_empty() {
-            if invoice_errors.len() != 1 {
-                panic!("Invalid number of invoice errors, expected 0 or 1.");
-            }
-            return Err(Error::from_invoice_error(&invoice_errors[0]));
+        if let Some(Some(e)) = Error::from_invoice_errors(&invoice_errors, 1)?.pop() {
+            return Err(e);
         }
     }
 
-    Ok(result.tx_id)
+    let tx_id = result
+        .tx_id
+        .ok_or(Error::MalformedResponse("no transaction id"))?;
+
+    Ok(details.into_receipt(tx_id, result.attempts))
 }
 
 async fn submit_payment_with_resolution(
-    client: &mut Client,
+    client: &Client,
     payment: &mut Payment,
     commitment: Option<Commitment>,
     sender_resolution: AccountResolution,
     destination_resolution: AccountResolution,
     create_destination_token_account: bool,
-) -> Result<SubmitTransactionResult> {
-    let config = client.internal.tx.get_service_config().await;
-    let mint = SolanaPublicKey::new(&config.token.as_ref().unwrap().value);
+) -> Result<(SubmitTransactionResult, PaymentDetails)> {
+    let config = client.internal.tx.get_service_config().await?;
+    let mint = get_mint_from_config(&config)?;
     let funder = match payment.subsidizer {
         Some(s) => s.public_key().to_solana_key(),
         None => get_subsidizer_from_config(&config)?,
     };
 
-    let mut result =
+    let mut submission =
         submit_payment_tx(client, payment, &config, commitment, None, None, None).await?;
 
-    if let Some(errors) = &result.errors {
+    if let Some(errors) = &submission.0.errors {
         if let Some(TransactionError::AccountDoesNotExist(_)) = &errors.tx_error {
             let mut transfer_sender: Option<PublicKey> = None;
             let mut resubmit = false;
@@ -109,14 +149,14 @@ async fn submit_payment_with_resolution(
             if sender_resolution == AccountResolution::Preferred {
                 let accounts = client
                     .resolve_token_accounts(&payment.sender.public_key())
-                    .await;
+                    .await?;
                 if !accounts.is_empty() {
                     transfer_sender = Some(accounts[0]);
                     resubmit = true;
                 }
             }
             if destination_resolution == AccountResolution::Preferred {
-                let accounts = client.resolve_token_accounts(&payment.destination).await;
+                let accounts = client.resolve_token_accounts(&payment.destination).await?;
                 if !accounts.is_empty() {
                     payment.destination = accounts[0];
                     resubmit = true;
@@ -130,7 +170,7 @@ async fn submit_payment_with_resolution(
                         create_account_and_pass_ownership(
                             client, &new_dest, &funder, &mint, &new_owner,
                         )
-                        .await,
+                        .await?,
                     );
 
                     let new_dest = temp_owner.public_key();
@@ -141,7 +181,7 @@ async fn submit_payment_with_resolution(
             }
 
             if resubmit {
-                result = submit_payment_tx(
+                submission = submit_payment_tx(
                     client,
                     payment,
                     &config,
@@ -155,39 +195,39 @@ async fn submit_payment_with_resolution(
         }
     }
 
-    Ok(result)
+    Ok(submission)
 }
 
 async fn create_account_and_pass_ownership(
-    client: &mut Client,
+    client: &Client,
     account: &SolanaPublicKey,
     funder: &SolanaPublicKey,
     mint: &SolanaPublicKey,
     new_owner: &SolanaPublicKey,
-) -> Vec<Instruction> {
+) -> Result<Vec<Instruction>> {
     let lamports = client
         .internal
         .tx
         .get_minimum_balance_for_rent_exemption()
-        .await;
+        .await?;
 
-    vec![
+    Ok(vec![
         create_account(funder, account, lamports, ACCOUNT_LEN, &spl_token::ID),
         initialize_account(&spl_token::ID, account, mint, account).unwrap(),
         set_close_authority(account, funder, account),
         set_owner_authority(account, new_owner, account),
-    ]
+    ])
 }
 
 async fn submit_payment_tx(
-    client: &mut Client,
+    client: &Client,
     payment: &Payment,
     config: &tx_pb::GetServiceConfigResponse,
     commitment: Option<Commitment>,
     transfer_sender: Option<&PublicKey>,
     create_instructions: Option<&mut Vec<Instruction>>,
     create_signer: Option<&PrivateKey>,
-) -> Result<SubmitTransactionResult> {
+) -> Result<(SubmitTransactionResult, PaymentDetails)> {
     let (mut signers, funder) =
         get_signers_and_funder(&payment.sender, payment.subsidizer.as_ref(), config)?;
 
@@ -197,23 +237,24 @@ async fn submit_payment_tx(
 
     let mut instructions = Vec::new();
     let mut invoice_list_proto: Option<model_pb_v3::InvoiceList> = None;
+    let mut invoice_hash: Option<Vec<u8>> = None;
 
+    let mut memo: Option<String> = None;
     if let Some(string_memo) = &payment.memo {
-        instructions.push(MemoProgram::memo(MemoParams::new(string_memo.to_string())))
+        memo = Some(string_memo.to_string());
     } else if client.app_index > 0 {
-        let mut foreign_key: Vec<u8> = [0; 29].to_vec();
-
-        if let Some(i) = &payment.invoice {
-            let il = InvoiceList::new(&[i.clone()]);
-            foreign_key = il.get_sha244_hash();
-            invoice_list_proto = Some(il.to_proto());
+        let invoices: Vec<Invoice> = payment.invoice.iter().cloned().collect();
+        let (foreign_key, invoice_list) = invoice_foreign_key(&invoices);
+        if invoice_list.is_some() {
+            invoice_hash = Some(foreign_key.clone());
         }
+        invoice_list_proto = invoice_list;
+
+        memo = Some(Memo::new(1, payment.tx_type, client.app_index, &foreign_key).to_base64());
+    }
 
-        instructions.push(kin_memo_instruction(
-            payment.tx_type,
-            client.app_index,
-            &foreign_key,
-        ));
+    if let Some(m) = &memo {
+        instructions.push(MemoProgram::memo(MemoParams::new(m.clone())));
     }
 
     if let Some(i) = create_instructions {
@@ -229,7 +270,7 @@ async fn submit_payment_tx(
 
     let mut tx = SolanaTransaction::new_with_payer(&instructions, Some(&funder));
 
-    client
+    let result = client
         .sign_and_submit_tx(
             &signers,
             &mut tx,
@@ -237,7 +278,18 @@ async fn submit_payment_tx(
             invoice_list_proto.as_ref(),
             payment.dedupe_id.as_ref(),
         )
-        .await
+        .await?;
+
+    let details = PaymentDetails {
+        sender,
+        destination: payment.destination,
+        destination_created: create_signer.is_some(),
+        fee_payer: PublicKey::new(funder.as_ref()),
+        invoice_hash,
+        memo,
+    };
+
+    Ok((result, details))
 }
 
 fn transfer_instruction(sender: &PublicKey, payment: &Payment) -> Instruction {
@@ -1,7 +1,7 @@
 use {
     crate::{
         gen::kin::agora::common::v3 as model_pb_v3, gen::kin::agora::common::v4 as model_pb_v4,
-        solana::token::program::is_transfer,
+        model::transaction_id::TransactionId, solana::token::program::is_transfer,
     },
     solana_sdk::transaction::Transaction as SolanaTransaction,
     substrate_stellar_sdk::types::{OperationBody, TransactionV0Envelope},
@@ -20,6 +20,12 @@ pub enum Error {
     #[error("Transaction data could not be found.")]
     TransactionNotFound,
 
+    #[error("Transaction was not confirmed before the timeout.")]
+    ConfirmationTimeout,
+
+    #[error("Invalid transaction id.")]
+    InvalidTransactionId,
+
     #[error("Malformed")]
     Malformed,
 
@@ -52,6 +58,48 @@ pub enum Error {
 
     #[error("No token accounts resolved for requested account ID.")]
     NoTokenAccounts,
+
+    #[error("App index required to use invoices.")]
+    AppIndexRequired,
+
+    #[error("Invalid memo.")]
+    InvalidMemo,
+
+    #[error("Invalid earn batch: {0}.")]
+    InvalidEarnBatch(&'static str),
+
+    #[error("Invalid endpoint: {0}.")]
+    InvalidEndpoint(String),
+
+    #[error("Invalid header: {0}.")]
+    InvalidHeader(String),
+
+    #[error("Failed to connect to Agora: {0}")]
+    Connection(#[from] tonic::transport::Error),
+
+    #[error("Agora request failed: {0}")]
+    Transport(#[from] tonic::Status),
+
+    #[error("Unexpected result from Agora: {0}.")]
+    UnexpectedResult(i32),
+
+    #[error("Malformed response from Agora: {0}.")]
+    MalformedResponse(&'static str),
+
+    #[error("Unknown invoice error reason: {0}.")]
+    UnknownInvoiceErrorReason(i32),
+
+    #[error("Unknown transaction error reason: {0}.")]
+    UnknownTransactionErrorReason(i32),
+
+    #[error("Invalid webhook JSON: {0}")]
+    WebhookJson(#[from] serde_json::Error),
+
+    #[error("Invalid webhook payload: {0}.")]
+    InvalidWebhookPayload(&'static str),
+
+    #[error("Invalid webhook signature.")]
+    InvalidWebhookSignature,
 }
 
 impl Error {
@@ -64,9 +112,30 @@ impl Error {
             Some(Reason::AlreadyPaid) => Error::AlreadyPaid,
             Some(Reason::WrongDestination) => Error::WrongDestination,
             Some(Reason::SkuNotFound) => Error::SkuNotFound,
-            _ => panic!("Unknown invoice error reason: {}", reason),
+            _ => Error::UnknownInvoiceErrorReason(reason),
         }
     }
+
+    /// Returns the error of each payment in a transaction, from the invoice errors of the
+    /// transaction.
+    ///
+    /// Invoice errors refer to payments by their index in the transaction, which is also the index of
+    /// their invoice in the invoice list.
+    pub fn from_invoice_errors(
+        invoice_errors: &[model_pb_v3::InvoiceError],
+        payment_count: usize,
+    ) -> Result<Vec<Option<Error>>, Error> {
+        let mut errors: Vec<Option<Error>> = (0..payment_count).map(|_| None).collect();
+
+        for invoice_error in invoice_errors {
+            match errors.get_mut(invoice_error.op_index as usize) {
+                Some(error) => *error = Some(Error::from_invoice_error(invoice_error)),
+                None => return Err(Error::MalformedResponse("invoice error index out of range")),
+            }
+        }
+
+        Ok(errors)
+    }
 }
 
 // TransactionError to Error conversion.
@@ -80,36 +149,38 @@ impl From<TransactionError> for Error {
 #[derive(ThisError, Debug, Clone, Eq, PartialEq)]
 pub enum TransactionError {
     #[error("Account does not exist.")]
-    AccountDoesNotExist(Option<Vec<u8>>),
+    AccountDoesNotExist(Option<TransactionId>),
 
     #[error("Bad nonce.")]
-    BadNonce(Option<Vec<u8>>),
+    BadNonce(Option<TransactionId>),
 
     #[error("Insufficient balance.")]
-    InsufficientBalance(Option<Vec<u8>>),
+    InsufficientBalance(Option<TransactionId>),
 
     #[error("Invalid signature.")]
-    InvalidSignature(Option<Vec<u8>>),
+    InvalidSignature(Option<TransactionId>),
 
     #[error("Already submitted.")]
-    AlreadySubmitted(Option<Vec<u8>>),
+    AlreadySubmitted(Option<TransactionId>),
 }
 
 impl TransactionError {
     fn from_proto(
         proto_error: &model_pb_v4::TransactionError,
-        tx_id: Option<Vec<u8>>,
-    ) -> Option<TransactionError> {
+        tx_id: Option<TransactionId>,
+    ) -> Result<Option<TransactionError>, Error> {
         use model_pb_v4::transaction_error::Reason;
 
-        match Reason::from_i32(proto_error.reason) {
+        let tx_error = match Reason::from_i32(proto_error.reason) {
             Some(Reason::None) => None,
             Some(Reason::Unauthorized) => Some(TransactionError::InvalidSignature(tx_id)),
             Some(Reason::BadNonce) => Some(TransactionError::BadNonce(tx_id)),
             Some(Reason::InsufficientFunds) => Some(TransactionError::InsufficientBalance(tx_id)),
             Some(Reason::InvalidAccount) => Some(TransactionError::AccountDoesNotExist(tx_id)),
-            _ => panic!("Unknown transaction error reason: {}.", proto_error.reason),
-        }
+            _ => return Err(Error::UnknownTransactionErrorReason(proto_error.reason)),
+        };
+
+        Ok(tx_error)
     }
 }
 
@@ -144,49 +215,68 @@ impl TransactionErrors {
     pub fn from_solana_tx(
         tx: &SolanaTransaction,
         proto_tx_error: &model_pb_v4::TransactionError,
-        tx_id: Option<Vec<u8>>,
-    ) -> TransactionErrors {
+        tx_id: Option<TransactionId>,
+    ) -> Result<TransactionErrors, Error> {
         let mut tx_errors = TransactionErrors::new();
 
-        let tx_error = TransactionError::from_proto(proto_tx_error, tx_id);
+        let tx_error = TransactionError::from_proto(proto_tx_error, tx_id)?;
         if tx_error.is_none() {
-            return tx_errors;
+            return Ok(tx_errors);
         }
 
         tx_errors.tx_error = tx_error.clone();
 
         let index = proto_tx_error.instruction_index;
         if index >= 0 {
-            tx_errors.op_errors =
-                tx_error_array(tx.message.instructions.len(), &tx_error, index as usize);
-            tx_errors.payment_errors = payment_errors_from_solana_tx(tx, &tx_error, index as usize);
+            let index = index as usize;
+            let len = tx.message.instructions.len();
+            if index >= len {
+                return Err(Error::MalformedResponse("instruction index out of range"));
+            }
+
+            let keys_len = tx.message.account_keys.len();
+            if tx
+                .message
+                .instructions
+                .iter()
+                .any(|instruction| instruction.program_id_index as usize >= keys_len)
+            {
+                return Err(Error::MalformedResponse("invalid program index"));
+            }
+
+            tx_errors.op_errors = tx_error_array(len, &tx_error, index);
+            tx_errors.payment_errors = payment_errors_from_solana_tx(tx, &tx_error, index);
         }
 
-        tx_errors
+        Ok(tx_errors)
     }
 
     pub fn from_stellar_tx(
         envelope: &TransactionV0Envelope,
         proto_tx_error: &model_pb_v4::TransactionError,
-    ) -> TransactionErrors {
+    ) -> Result<TransactionErrors, Error> {
         let mut tx_errors = TransactionErrors::new();
 
-        let tx_error = TransactionError::from_proto(proto_tx_error, None);
+        let tx_error = TransactionError::from_proto(proto_tx_error, None)?;
         if tx_error.is_none() {
-            return tx_errors;
+            return Ok(tx_errors);
         }
 
         tx_errors.tx_error = tx_error.clone();
 
         let index = proto_tx_error.instruction_index;
         if index >= 0 {
-            tx_errors.op_errors =
-                tx_error_array(envelope.tx.operations.len(), &tx_error, index as usize);
-            tx_errors.payment_errors =
-                payment_errors_from_stellar_tx(envelope, &tx_error, index as usize);
+            let index = index as usize;
+            let len = envelope.tx.operations.len();
+            if index >= len {
+                return Err(Error::MalformedResponse("operation index out of range"));
+            }
+
+            tx_errors.op_errors = tx_error_array(len, &tx_error, index);
+            tx_errors.payment_errors = payment_errors_from_stellar_tx(envelope, &tx_error, index);
         }
 
-        tx_errors
+        Ok(tx_errors)
     }
 }
 
@@ -267,3 +357,62 @@ fn payment_errors_from_stellar_tx(
 fn is_payment(op_body: &OperationBody) -> bool {
     matches!(op_body, OperationBody::Payment(_))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_invoice_error_with_unknown_reason() {
+        let invoice_error = model_pb_v3::InvoiceError {
+            reason: 100,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Error::from_invoice_error(&invoice_error),
+            Error::UnknownInvoiceErrorReason(100)
+        ));
+    }
+
+    #[test]
+    fn from_invoice_errors_by_op_index() {
+        use model_pb_v3::invoice_error::Reason;
+
+        let invoice_errors = [
+            model_pb_v3::InvoiceError {
+                op_index: 2,
+                reason: Reason::SkuNotFound as i32,
+                ..Default::default()
+            },
+            model_pb_v3::InvoiceError {
+                op_index: 0,
+                reason: Reason::AlreadyPaid as i32,
+                ..Default::default()
+            },
+        ];
+
+        let errors = Error::from_invoice_errors(&invoice_errors, 3).unwrap();
+        assert!(matches!(errors[0], Some(Error::AlreadyPaid)));
+        assert!(errors[1].is_none());
+        assert!(matches!(errors[2], Some(Error::SkuNotFound)));
+
+        assert!(matches!(
+            Error::from_invoice_errors(&invoice_errors, 2),
+            Err(Error::MalformedResponse(_))
+        ));
+    }
+
+    #[test]
+    fn transaction_error_from_proto_with_unknown_reason() {
+        let proto_error = model_pb_v4::TransactionError {
+            reason: 100,
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            TransactionError::from_proto(&proto_error, None),
+            Err(Error::UnknownTransactionErrorReason(100))
+        ));
+    }
+}
@@ -16,14 +16,20 @@ impl PublicKey {
 
     /// Returns a public key from the provided slice.
     pub fn new(slice: &[u8]) -> PublicKey {
-        PublicKey::try_from(slice).unwrap()
+        PublicKey::try_from(slice).unwrap_or_else(|_| {
+            panic!(
+                "Expected a {} byte public key. Received {} bytes.",
+                PublicKey::LEN,
+                slice.len()
+            )
+        })
     }
 
     /// Returns a public key from a base58-encoded string.
     pub fn from_base58(address: &str) -> PublicKey {
         let decoded = bs58::decode(address).into_vec().unwrap();
 
-        PublicKey::try_from(&decoded).unwrap()
+        PublicKey::new(&decoded)
     }
 
     /// Returns a public key from a Stellar-encoded address.
@@ -69,17 +75,9 @@ impl TryFrom<&[u8]> for PublicKey {
     type Error = ();
 
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
-        if slice.len() == PublicKey::LEN {
-            let bytes: [u8; 32] = slice.try_into().unwrap();
+        let bytes: [u8; 32] = slice.try_into().map_err(|_| ())?;
 
-            Ok(PublicKey(bytes))
-        } else {
-            panic!(
-                "Expected a {} byte public key. Received {} bytes.",
-                PublicKey::LEN,
-                slice.len()
-            )
-        }
+        Ok(PublicKey(bytes))
     }
 }
 
@@ -1,10 +1,15 @@
 #![allow(dead_code)]
 #![allow(clippy::wrong_self_convention)]
+#![allow(clippy::result_large_err)]
 
 mod client;
 mod error;
 mod gen;
 mod key;
 mod model;
+mod retry;
 mod solana;
+#[cfg(any(test, feature = "test-utils"))]
+mod test_utils;
 mod utils;
+mod webhook;
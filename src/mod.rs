@@ -3,5 +3,9 @@ pub mod error;
 pub mod gen;
 pub mod key;
 pub mod model;
+pub mod retry;
 pub mod solana;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod utils;
+pub mod webhook;
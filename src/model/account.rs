@@ -0,0 +1,78 @@
+use {
+    crate::{
+        error::Error,
+        gen::kin::agora::{account::v4 as account_pb, common::v4 as model_pb_v4},
+        key::public::PublicKey,
+    },
+    std::convert::TryFrom,
+};
+
+/// The details of a Kin token account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountInfo {
+    pub account_id: PublicKey,
+
+    /// The owner of the account. Not set when Agora resolves token accounts without their info.
+    pub owner: Option<PublicKey>,
+
+    /// The account that may close this account, if any, and if known.
+    pub close_authority: Option<PublicKey>,
+
+    /// The balance of the account, in quarks.
+    pub balance: i64,
+}
+
+impl AccountInfo {
+    /// Returns an AccountInfo containing only an account id.
+    pub fn with_id_only(account_id: PublicKey) -> AccountInfo {
+        AccountInfo {
+            account_id,
+            owner: None,
+            close_authority: None,
+            balance: 0,
+        }
+    }
+
+    /// Returns AccountInfo object from an account info received from Agora.
+    pub fn from_proto(info: &account_pb::AccountInfo) -> Result<AccountInfo, Error> {
+        let account_id = match &info.account_id {
+            Some(id) => public_key_from_proto(id)?,
+            None => return Err(Error::MalformedResponse("account info has no account id")),
+        };
+
+        Ok(AccountInfo {
+            account_id,
+            owner: info.owner.as_ref().map(public_key_from_proto).transpose()?,
+            close_authority: info
+                .close_authority
+                .as_ref()
+                .map(public_key_from_proto)
+                .transpose()?,
+            balance: info.balance,
+        })
+    }
+}
+
+/// The combined balance of all of the token accounts of an owner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregateBalance {
+    /// The sum of the balances of the accounts, in quarks.
+    pub total: i64,
+
+    /// The token accounts that make up the total.
+    pub accounts: Vec<AccountInfo>,
+}
+
+impl AggregateBalance {
+    pub fn new(accounts: Vec<AccountInfo>) -> AggregateBalance {
+        AggregateBalance {
+            total: accounts.iter().map(|account| account.balance).sum(),
+            accounts,
+        }
+    }
+}
+
+/// Returns the public key of an account id received from Agora.
+pub(crate) fn public_key_from_proto(id: &model_pb_v4::SolanaAccountId) -> Result<PublicKey, Error> {
+    PublicKey::try_from(&id.value).map_err(|_| Error::MalformedResponse("invalid account id"))
+}
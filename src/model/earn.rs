@@ -0,0 +1,88 @@
+use {
+    crate::{
+        error::Error,
+        key::{private::PrivateKey, public::PublicKey},
+        model::{invoice::Invoice, transaction_id::TransactionId},
+    },
+    std::sync::Arc,
+};
+
+/// Represents a payment to a destination, as part of an earn batch.
+#[derive(Debug, Clone)]
+pub struct Earn {
+    pub destination: PublicKey,
+    pub quarks: u64,
+    pub invoice: Option<Invoice>,
+}
+
+impl Earn {
+    pub fn new(destination: PublicKey, quarks: u64) -> Earn {
+        Earn {
+            destination,
+            quarks,
+            invoice: None,
+        }
+    }
+
+    pub fn set_invoice(&mut self, invoice: Invoice) {
+        self.invoice = Some(invoice);
+    }
+}
+
+/// Represents a batch of earns from a single sender, to be submitted in as few transactions as
+/// possible.
+///
+/// Either all or none of the earns may have an invoice, and invoices cannot be combined with a memo.
+#[derive(Clone)]
+pub struct EarnBatch {
+    pub sender: PrivateKey,
+    pub earns: Vec<Earn>,
+    pub subsidizer: Option<PrivateKey>,
+    pub memo: Option<String>,
+}
+
+impl EarnBatch {
+    pub fn new(sender: PrivateKey, earns: Vec<Earn>) -> EarnBatch {
+        EarnBatch {
+            sender,
+            earns,
+            subsidizer: None,
+            memo: None,
+        }
+    }
+
+    pub fn set_subsidizer(&mut self, subsidizer: PrivateKey) {
+        self.subsidizer = Some(subsidizer);
+    }
+
+    pub fn set_memo(&mut self, memo: &str) {
+        self.memo = Some(memo.to_string());
+    }
+}
+
+/// The outcome of a single earn in a batch.
+#[derive(Debug, Clone)]
+pub struct EarnResult {
+    pub earn: Earn,
+
+    /// The id of the transaction the earn was submitted in, if it succeeded.
+    pub tx_id: Option<TransactionId>,
+
+    /// The reason the earn failed. Earns in the same transaction may share an error.
+    pub error: Option<Arc<Error>>,
+
+    /// The number of times the transaction of the earn was signed and submitted, including
+    /// resubmissions after a bad nonce. Zero if the earn was not submitted, or if the number is not
+    /// known because the submission failed.
+    pub attempts: u32,
+}
+
+/// The outcome of an earn batch.
+///
+/// Submission stops at the first transaction that fails. The earns that were not submitted are in
+/// `failed`, without an error.
+#[derive(Debug, Clone, Default)]
+pub struct EarnBatchResult {
+    pub succeeded: Vec<EarnResult>,
+    pub failed: Vec<EarnResult>,
+}
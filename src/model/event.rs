@@ -0,0 +1,54 @@
+use {
+    crate::{
+        error::Error,
+        gen::kin::agora::account::v4 as account_pb,
+        model::{account::AccountInfo, transaction::TransactionData},
+    },
+    solana_sdk::transaction::Transaction as SolanaTransaction,
+};
+
+/// An event related to an account, received while watching it.
+#[derive(Debug)]
+pub enum Event {
+    /// The balance or authorities of the account changed.
+    AccountUpdate(AccountInfo),
+
+    /// A transaction involving the account was submitted.
+    Transaction(TransactionData),
+}
+
+impl Event {
+    /// Returns an Event from an event received from Agora, or None if the event is of an unknown
+    /// type.
+    pub fn from_proto(event: &account_pb::Event) -> Result<Option<Event>, Error> {
+        use account_pb::event::Type;
+
+        let event = match &event.r#type {
+            Some(Type::AccountUpdateEvent(update)) => {
+                let info = update
+                    .account_info
+                    .as_ref()
+                    .ok_or(Error::MalformedResponse(
+                        "account update has no account info",
+                    ))?;
+
+                Event::AccountUpdate(AccountInfo::from_proto(info)?)
+            }
+            Some(Type::TransactionEvent(event)) => {
+                let raw = event.transaction.as_ref().ok_or(Error::MalformedResponse(
+                    "transaction event has no transaction",
+                ))?;
+                let tx: SolanaTransaction = bincode::deserialize(&raw.value)
+                    .map_err(|_| Error::MalformedResponse("invalid Solana transaction"))?;
+
+                Event::Transaction(TransactionData::from_solana_tx(
+                    &tx,
+                    event.transaction_error.as_ref(),
+                )?)
+            }
+            None => return Ok(None),
+        };
+
+        Ok(Some(event))
+    }
+}
@@ -1,5 +1,5 @@
 use {
-    crate::model::transaction_type::TransactionType,
+    crate::{error::Error, model::transaction_type::TransactionType},
     base64,
     std::convert::{TryFrom, TryInto},
     substrate_stellar_sdk::Memo as StellarMemo,
@@ -76,7 +76,13 @@ impl Memo {
 
     /// Returns a memo from a slice.
     pub fn from_slice(slice: &[u8]) -> Memo {
-        Memo::try_from(slice).unwrap()
+        Memo::try_from(slice).unwrap_or_else(|_| {
+            panic!(
+                "Expected a memo of size {} bytes. Received {} bytes.",
+                Memo::LEN,
+                slice.len()
+            )
+        })
     }
 
     /// Returns a memo from a base64 encoded string.
@@ -84,18 +90,18 @@ impl Memo {
     /// # Arguments
     /// * `s` - A string slice that holds the base64 encoded memo.
     /// * `strict` - Whether or not to run strict validation on the memo.
-    pub fn from_base64(s: &str, strict: bool) -> Result<Memo, base64::DecodeError> {
-        let raw = base64::decode(s).unwrap();
+    pub fn from_base64(s: &str, strict: bool) -> Result<Memo, Error> {
+        let raw = base64::decode(s).map_err(|_| Error::InvalidMemo)?;
 
-        let memo = Memo::from_slice(&raw);
+        let memo = Memo::try_from(raw.as_slice()).map_err(|_| Error::InvalidMemo)?;
         if !memo.is_valid(strict) {
-            panic!("Invalid memo.");
+            return Err(Error::InvalidMemo);
         }
 
         Ok(memo)
     }
 
-    /// Returns an Agora memo from a Stellar hash memo.
+    /// Returns an Agora memo from a Stellar hash memo, or None if the memo is not a valid Agora memo.
     ///
     /// # Arguments
     /// * `stellar_memo` - The Stellar hash memo to convert into an Agora memo.
@@ -105,7 +111,7 @@ impl Memo {
             StellarMemo::MemoHash(hash) => {
                 let memo = Memo::from_slice(hash);
                 if !memo.is_valid(strict) {
-                    panic!("Not a valid Agora memo.")
+                    return None;
                 }
 
                 Some(memo)
@@ -186,17 +192,9 @@ impl TryFrom<&[u8]> for Memo {
     type Error = ();
 
     fn try_from(slice: &[u8]) -> Result<Self, Self::Error> {
-        if slice.len() <= Memo::LEN {
-            let bytes: [u8; 32] = slice.try_into().unwrap();
+        let bytes: [u8; 32] = slice.try_into().map_err(|_| ())?;
 
-            Ok(Memo { bytes })
-        } else {
-            panic!(
-                "Expected a memo of size {} bytes or smaller. Received {} bytes.",
-                Memo::LEN,
-                slice.len()
-            )
-        }
+        Ok(Memo { bytes })
     }
 }
 
@@ -324,6 +322,19 @@ mod tests {
         assert!(m.is_none());
     }
 
+    #[test]
+    fn from_base64_with_invalid_memo() {
+        // Valid base64, but not an Agora memo.
+        assert!(Memo::from_base64("aGVsbG8gd29ybGQ=", false).is_err());
+
+        // Not base64.
+        assert!(Memo::from_base64("hello world", false).is_err());
+
+        // Unsupported version in strict mode.
+        let m = Memo::new(3, TransactionType::Earn, 1, &[]);
+        assert!(Memo::from_base64(&m.to_base64(), true).is_err());
+    }
+
     #[test]
     fn parsing_bytes_generated_by_go_sdk() {
         test_parsing_memo_with_empty_fk();
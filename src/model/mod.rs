@@ -1,5 +1,9 @@
+pub mod account;
+pub mod earn;
+pub mod event;
 pub mod invoice;
 pub mod memo;
 pub mod payment;
 pub mod transaction;
+pub mod transaction_id;
 pub mod transaction_type;
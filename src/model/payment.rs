@@ -1,6 +1,6 @@
 use crate::{
     key::{private::PrivateKey, public::PublicKey},
-    model::{invoice::Invoice, transaction_type::TransactionType},
+    model::{invoice::Invoice, transaction_id::TransactionId, transaction_type::TransactionType},
 };
 
 /// Represents a payment retrieved from history.
@@ -80,3 +80,33 @@ impl Payment {
         self.dedupe_id = Some(dedupe_id);
     }
 }
+
+/// Describes how a submitted payment was made on chain.
+#[derive(Debug, Clone)]
+pub struct PaymentReceipt {
+    pub tx_id: TransactionId,
+
+    /// The token account the payment was transferred from, after account resolution.
+    pub sender: PublicKey,
+
+    /// The token account the payment was transferred to, after account resolution.
+    pub destination: PublicKey,
+
+    /// Whether the destination token account was created by the transaction.
+    pub destination_created: bool,
+
+    /// The account that paid the transaction fees.
+    pub fee_payer: PublicKey,
+
+    /// The SHA-224 hash of the invoice list, used as the memo foreign key, if the payment has an
+    /// invoice.
+    pub invoice_hash: Option<Vec<u8>>,
+
+    /// The memo of the transaction: either the text memo of the payment, or a base64-encoded Agora
+    /// memo.
+    pub memo: Option<String>,
+
+    /// The number of times the transaction was signed and submitted, including resubmissions after
+    /// a bad nonce.
+    pub attempts: u32,
+}
@@ -1,18 +1,22 @@
 use {
     crate::{
-        error::TransactionErrors,
-        gen::kin::agora::transaction::v4 as tx_pb,
+        error::{Error, TransactionErrors},
+        gen::kin::agora::{common::v4 as model_pb_v4, transaction::v4 as tx_pb},
         key::public::PublicKey,
         model::{
-            invoice::Invoice, memo::Memo, payment::ReadOnlyPayment,
-            transaction_type::TransactionType,
+            account::public_key_from_proto, invoice::Invoice, memo::Memo, payment::ReadOnlyPayment,
+            transaction_id::TransactionId, transaction_type::TransactionType,
         },
         solana::memo::program::{MemoInstruction, MemoProgram},
     },
     bincode,
     num_derive::FromPrimitive,
     num_traits::FromPrimitive,
-    solana_sdk::transaction::Transaction as SolanaTransaction,
+    solana_sdk::{
+        instruction::CompiledInstruction, pubkey::Pubkey as SolanaPublicKey,
+        transaction::Transaction as SolanaTransaction,
+    },
+    spl_token::instruction::TokenInstruction,
     substrate_stellar_sdk::{types::TransactionV0Envelope, Memo as StellarMemo, XdrCodec},
 };
 
@@ -42,7 +46,7 @@ impl TransactionState {
 /// Contains both metadata and payment data related to a blockchain transaction.
 #[derive(Debug)]
 pub struct TransactionData {
-    pub tx_id: Vec<u8>,
+    pub tx_id: TransactionId,
     pub tx_state: TransactionState,
     pub payments: Vec<ReadOnlyPayment>,
     pub errors: Option<TransactionErrors>,
@@ -50,7 +54,7 @@ pub struct TransactionData {
 
 impl TransactionData {
     /// Returns TransactionData object from provided id and state.
-    pub fn new(tx_id: Vec<u8>, tx_state: TransactionState) -> TransactionData {
+    pub fn new(tx_id: TransactionId, tx_state: TransactionState) -> TransactionData {
         TransactionData {
             tx_id,
             tx_state,
@@ -63,10 +67,12 @@ impl TransactionData {
     pub fn from_proto(
         item: &tx_pb::HistoryItem,
         state: tx_pb::get_transaction_response::State,
-    ) -> TransactionData {
+    ) -> Result<TransactionData, Error> {
         if let Some(invoice_list) = &item.invoice_list {
             if invoice_list.invoices.len() != item.payments.len() {
-                panic!("Number of invoices does not match number of payments.");
+                return Err(Error::MalformedResponse(
+                    "number of invoices does not match number of payments",
+                ));
             }
         }
 
@@ -77,35 +83,24 @@ impl TransactionData {
         if let Some(raw_tx) = &item.raw_transaction {
             match raw_tx {
                 tx_pb::history_item::RawTransaction::SolanaTransaction(tx) => {
-                    let solana_tx: SolanaTransaction = bincode::deserialize(&tx.value).unwrap();
+                    let solana_tx: SolanaTransaction = bincode::deserialize(&tx.value)
+                        .map_err(|_| Error::MalformedResponse("invalid Solana transaction"))?;
 
                     // Memo.
-                    let program_id = solana_tx.message().program_id(0);
-                    if let Some(program_id) = program_id {
-                        if program_id.eq(&MemoProgram::id()) {
-                            let memo_params =
-                                MemoInstruction::decode_memo(&solana_tx.message().instructions[0]);
-                            let agora_memo = Memo::from_base64(&memo_params.data, false);
-                            match agora_memo {
-                                Ok(memo) => tx_type = memo.tx_type(),
-                                _ => {
-                                    // Not a valid agora memo.
-                                    string_memo = Some(memo_params.data);
-                                }
-                            }
-                        }
-                    }
+                    let (memo_tx_type, memo) = memo_from_solana_tx(&solana_tx)?;
+                    tx_type = memo_tx_type;
+                    string_memo = memo;
 
                     // Errors.
                     if let Some(tx_error) = &item.transaction_error {
                         errors = Some(TransactionErrors::from_solana_tx(
                             &solana_tx, tx_error, None,
-                        ));
+                        )?);
                     }
                 }
                 tx_pb::history_item::RawTransaction::StellarTransaction(tx) => {
-                    let envelope =
-                        TransactionV0Envelope::from_xdr(tx.envelope_xdr.clone()).unwrap();
+                    let envelope = TransactionV0Envelope::from_xdr(tx.envelope_xdr.clone())
+                        .map_err(|_| Error::MalformedResponse("invalid Stellar envelope"))?;
                     let stellar_memo = &envelope.tx.memo;
                     let agora_memo = Memo::from_stellar(stellar_memo, true);
 
@@ -114,13 +109,13 @@ impl TransactionData {
                         None => {
                             if let StellarMemo::MemoText(text) = stellar_memo {
                                 let text_bytes = text.get_vec().to_vec();
-                                string_memo = Some(String::from_utf8(text_bytes).unwrap());
+                                string_memo = Some(String::from_utf8_lossy(&text_bytes).into());
                             }
                         }
                     }
 
                     if let Some(tx_error) = &item.transaction_error {
-                        errors = Some(TransactionErrors::from_stellar_tx(&envelope, tx_error));
+                        errors = Some(TransactionErrors::from_stellar_tx(&envelope, tx_error)?);
                     }
                 }
             }
@@ -130,13 +125,13 @@ impl TransactionData {
         let mut payments: Vec<ReadOnlyPayment> = Vec::new();
         for (i, payment) in item.payments.iter().enumerate() {
             let source_key = match &payment.source {
-                Some(source) => PublicKey::new(&source.value),
-                None => panic!("No sender."),
+                Some(source) => public_key_from_proto(source)?,
+                None => return Err(Error::MalformedResponse("payment has no sender")),
             };
 
             let destination_key = match &payment.destination {
-                Some(destination) => PublicKey::new(&destination.value),
-                None => panic!("No destination."),
+                Some(destination) => public_key_from_proto(destination)?,
+                None => return Err(Error::MalformedResponse("payment has no destination")),
             };
 
             let mut read_only_payment =
@@ -153,14 +148,209 @@ impl TransactionData {
             payments.push(read_only_payment);
         }
 
-        let tx_id = item.transaction_id.as_ref().unwrap().value.clone();
+        let tx_id = match &item.transaction_id {
+            Some(id) => TransactionId::new(&id.value),
+            None => {
+                return Err(Error::MalformedResponse(
+                    "history item has no transaction id",
+                ))
+            }
+        };
         let tx_state = TransactionState::from_proto(state);
 
-        TransactionData {
+        Ok(TransactionData {
             tx_id,
             tx_state,
             payments,
             errors,
+        })
+    }
+
+    /// Returns TransactionData object from a Solana transaction and the error it failed with, if any.
+    ///
+    /// Payments are parsed from the transfer instructions of the transaction.
+    pub fn from_solana_tx(
+        tx: &SolanaTransaction,
+        tx_error: Option<&model_pb_v4::TransactionError>,
+    ) -> Result<TransactionData, Error> {
+        let tx_id = match tx.signatures.first() {
+            Some(signature) => TransactionId::from(*signature),
+            None => return Err(Error::MalformedResponse("transaction has no signatures")),
+        };
+
+        let errors = match tx_error {
+            Some(tx_error) => Some(TransactionErrors::from_solana_tx(
+                tx,
+                tx_error,
+                Some(tx_id.clone()),
+            )?),
+            None => None,
+        };
+        let tx_state = match &errors {
+            Some(errors) if errors.tx_error.is_some() => TransactionState::Failed,
+            _ => TransactionState::Success,
+        };
+
+        let (tx_type, string_memo) = memo_from_solana_tx(tx)?;
+
+        // Payments.
+        let message = &tx.message;
+        let mut payments: Vec<ReadOnlyPayment> = Vec::new();
+        for instruction in &message.instructions {
+            if program_id(tx, instruction)?.ne(&spl_token::id()) {
+                continue;
+            }
+
+            let amount = match TokenInstruction::unpack(&instruction.data) {
+                Ok(TokenInstruction::Transfer { amount }) => amount,
+                _ => continue,
+            };
+            if instruction.accounts.len() < 2 {
+                return Err(Error::MalformedResponse("transfer has too few accounts"));
+            }
+
+            let mut read_only_payment = ReadOnlyPayment::new(
+                account(tx, instruction, 0)?,
+                account(tx, instruction, 1)?,
+                tx_type,
+                amount as i64,
+            );
+            read_only_payment.memo = string_memo.clone();
+
+            payments.push(read_only_payment);
         }
+
+        Ok(TransactionData {
+            tx_id,
+            tx_state,
+            payments,
+            errors,
+        })
+    }
+}
+
+/// Returns the transaction type of a Solana transaction, or its text memo if the transaction does not
+/// have an Agora memo.
+fn memo_from_solana_tx(tx: &SolanaTransaction) -> Result<(TransactionType, Option<String>), Error> {
+    if let Some(instruction) = tx.message.instructions.first() {
+        if program_id(tx, instruction)?.eq(&MemoProgram::id()) {
+            let memo_params = MemoInstruction::decode_memo(instruction)
+                .map_err(|_| Error::MalformedResponse("memo is not valid UTF-8"))?;
+            let agora_memo = Memo::from_base64(&memo_params.data, false);
+            match agora_memo {
+                Ok(memo) => return Ok((memo.tx_type(), None)),
+                _ => {
+                    // Not a valid agora memo.
+                    return Ok((TransactionType::Unknown, Some(memo_params.data)));
+                }
+            }
+        }
+    }
+
+    Ok((TransactionType::Unknown, None))
+}
+
+/// Returns the program id of an instruction of a Solana transaction.
+fn program_id(
+    tx: &SolanaTransaction,
+    instruction: &CompiledInstruction,
+) -> Result<SolanaPublicKey, Error> {
+    tx.message
+        .account_keys
+        .get(instruction.program_id_index as usize)
+        .copied()
+        .ok_or(Error::MalformedResponse("invalid program index"))
+}
+
+/// Returns the account at the given index of an instruction of a Solana transaction.
+fn account(
+    tx: &SolanaTransaction,
+    instruction: &CompiledInstruction,
+    index: usize,
+) -> Result<PublicKey, Error> {
+    instruction
+        .accounts
+        .get(index)
+        .and_then(|i| tx.message.account_keys.get(*i as usize))
+        .map(|key| PublicKey::new(key.as_ref()))
+        .ok_or(Error::MalformedResponse("invalid account index"))
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            key::private::PrivateKey,
+            solana::memo::program::{MemoParams, MemoProgram},
+        },
+        solana_sdk::signature::Signature,
+    };
+
+    fn transfer_tx() -> SolanaTransaction {
+        let owner = PrivateKey::rand().public_key().to_solana_key();
+        let source = PrivateKey::rand().public_key().to_solana_key();
+        let dest = PrivateKey::rand().public_key().to_solana_key();
+
+        let instructions = vec![
+            MemoProgram::memo(MemoParams::new("memo".to_string())),
+            spl_token::instruction::transfer(&spl_token::id(), &source, &dest, &owner, &[], 10)
+                .unwrap(),
+        ];
+
+        let mut tx = SolanaTransaction::new_with_payer(&instructions, Some(&owner));
+        tx.signatures[0] = Signature::new(&[1; 64]);
+        tx
+    }
+
+    #[test]
+    fn from_solana_tx() {
+        let data = TransactionData::from_solana_tx(&transfer_tx(), None).unwrap();
+        assert_eq!(data.tx_state, TransactionState::Success);
+        assert_eq!(data.payments.len(), 1);
+        assert_eq!(data.payments[0].quarks, 10);
+        assert_eq!(data.payments[0].memo.as_deref(), Some("memo"));
+    }
+
+    #[test]
+    fn from_solana_tx_with_out_of_range_index() {
+        let keys_len = transfer_tx().message.account_keys.len() as u8;
+
+        let mut tx = transfer_tx();
+        tx.message.instructions[1].program_id_index = keys_len;
+        assert!(matches!(
+            TransactionData::from_solana_tx(&tx, None),
+            Err(Error::MalformedResponse(_))
+        ));
+
+        let mut tx = transfer_tx();
+        tx.message.instructions[1].accounts[1] = keys_len;
+        assert!(matches!(
+            TransactionData::from_solana_tx(&tx, None),
+            Err(Error::MalformedResponse(_))
+        ));
+
+        let mut tx = transfer_tx();
+        tx.message.instructions[0].program_id_index = keys_len;
+        let tx_error = model_pb_v4::TransactionError {
+            reason: model_pb_v4::transaction_error::Reason::BadNonce as i32,
+            instruction_index: 1,
+            raw: Vec::new(),
+        };
+        assert!(matches!(
+            TransactionData::from_solana_tx(&tx, Some(&tx_error)),
+            Err(Error::MalformedResponse(_))
+        ));
+    }
+
+    #[test]
+    fn from_solana_tx_with_invalid_utf8_memo() {
+        let mut tx = transfer_tx();
+        tx.message.instructions[0].data = vec![0xff, 0xfe];
+
+        assert!(matches!(
+            TransactionData::from_solana_tx(&tx, None),
+            Err(Error::MalformedResponse(_))
+        ));
     }
 }
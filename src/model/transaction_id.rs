@@ -0,0 +1,128 @@
+use {
+    crate::error::Error,
+    solana_sdk::signature::{Signature, SIGNATURE_BYTES},
+    std::{convert::TryFrom, fmt, str::FromStr},
+};
+
+/// The length of the hash of a Stellar transaction, used as its id.
+pub const STELLAR_HASH_BYTES: usize = 32;
+
+/// Identifies a transaction: the first signature of a Solana transaction, or the hash of a Stellar
+/// transaction.
+///
+/// Transaction ids are displayed and parsed as base58.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TransactionId(Vec<u8>);
+
+impl TransactionId {
+    pub fn new(bytes: &[u8]) -> TransactionId {
+        TransactionId(bytes.to_vec())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+
+    /// Returns the Solana signature of the transaction, if the id is a signature.
+    pub fn to_signature(&self) -> Option<Signature> {
+        if self.0.len() != SIGNATURE_BYTES {
+            return None;
+        }
+
+        Some(Signature::new(&self.0))
+    }
+
+    /// Returns the hash of the Stellar transaction, if the id is a hash.
+    pub fn to_stellar_hash(&self) -> Option<[u8; STELLAR_HASH_BYTES]> {
+        <[u8; STELLAR_HASH_BYTES]>::try_from(self.0.as_slice()).ok()
+    }
+}
+
+impl From<Vec<u8>> for TransactionId {
+    fn from(bytes: Vec<u8>) -> TransactionId {
+        TransactionId(bytes)
+    }
+}
+
+impl From<Signature> for TransactionId {
+    fn from(signature: Signature) -> TransactionId {
+        TransactionId::new(signature.as_ref())
+    }
+}
+
+impl From<[u8; STELLAR_HASH_BYTES]> for TransactionId {
+    fn from(hash: [u8; STELLAR_HASH_BYTES]) -> TransactionId {
+        TransactionId::new(&hash)
+    }
+}
+
+impl AsRef<[u8]> for TransactionId {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl fmt::Display for TransactionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&bs58::encode(&self.0).into_string())
+    }
+}
+
+impl FromStr for TransactionId {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<TransactionId, Error> {
+        let bytes = bs58::decode(s)
+            .into_vec()
+            .map_err(|_| Error::InvalidTransactionId)?;
+        if bytes.len() != SIGNATURE_BYTES && bytes.len() != STELLAR_HASH_BYTES {
+            return Err(Error::InvalidTransactionId);
+        }
+
+        Ok(TransactionId(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base58_round_trip() {
+        let id = TransactionId::from(Signature::new(&[7; SIGNATURE_BYTES]));
+        let encoded = id.to_string();
+
+        assert_eq!(encoded, bs58::encode([7; SIGNATURE_BYTES]).into_string());
+        assert_eq!(encoded.parse::<TransactionId>().unwrap(), id);
+    }
+
+    #[test]
+    fn from_str_invalid() {
+        assert!(matches!(
+            "0OIl".parse::<TransactionId>(),
+            Err(Error::InvalidTransactionId)
+        ));
+
+        let short = bs58::encode([1; 16]).into_string();
+        assert!(matches!(
+            short.parse::<TransactionId>(),
+            Err(Error::InvalidTransactionId)
+        ));
+    }
+
+    #[test]
+    fn conversions() {
+        let signature = Signature::new(&[1; SIGNATURE_BYTES]);
+        let id = TransactionId::from(signature);
+        assert_eq!(id.to_signature(), Some(signature));
+        assert_eq!(id.to_stellar_hash(), None);
+
+        let id = TransactionId::from([2; STELLAR_HASH_BYTES]);
+        assert_eq!(id.to_stellar_hash(), Some([2; STELLAR_HASH_BYTES]));
+        assert_eq!(id.to_signature(), None);
+    }
+}
@@ -0,0 +1,120 @@
+use std::time::Duration;
+
+/// Determines how long to wait before retrying a failed call.
+pub trait BackoffStrategy: Send + Sync {
+    /// Returns the amount of time to wait after the given number of attempts.
+    fn get_backoff(&self, attempts: u32) -> Duration;
+}
+
+/// Waits the same amount of time between every attempt.
+pub struct Constant {
+    pub interval: Duration,
+}
+
+impl BackoffStrategy for Constant {
+    fn get_backoff(&self, _attempts: u32) -> Duration {
+        self.interval
+    }
+}
+
+/// Waits `base * attempts` between attempts.
+pub struct Linear {
+    pub base: Duration,
+}
+
+impl BackoffStrategy for Linear {
+    fn get_backoff(&self, attempts: u32) -> Duration {
+        duration_from_secs(self.base.as_secs_f64() * attempts as f64)
+    }
+}
+
+/// Waits `base * factor^(attempts - 1)` between attempts.
+pub struct Exponential {
+    pub base: Duration,
+    pub factor: f64,
+}
+
+impl BackoffStrategy for Exponential {
+    fn get_backoff(&self, attempts: u32) -> Duration {
+        let exponent = attempts.saturating_sub(1).min(i32::MAX as u32) as i32;
+
+        duration_from_secs(self.base.as_secs_f64() * self.factor.powi(exponent))
+    }
+}
+
+/// Waits `base * 2^(attempts - 1)` between attempts.
+pub struct BinaryExponential {
+    pub base: Duration,
+}
+
+impl BackoffStrategy for BinaryExponential {
+    fn get_backoff(&self, attempts: u32) -> Duration {
+        Exponential {
+            base: self.base,
+            factor: 2.0,
+        }
+        .get_backoff(attempts)
+    }
+}
+
+/// Converts seconds to a Duration, saturating instead of panicking on overflow.
+pub(crate) fn duration_from_secs(secs: f64) -> Duration {
+    if !secs.is_finite() || secs >= u64::MAX as f64 {
+        Duration::MAX
+    } else if secs <= 0.0 {
+        Duration::from_secs(0)
+    } else {
+        Duration::from_secs_f64(secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant() {
+        let backoff = Constant {
+            interval: Duration::from_millis(500),
+        };
+
+        for attempts in 1..10 {
+            assert_eq!(backoff.get_backoff(attempts), Duration::from_millis(500));
+        }
+    }
+
+    #[test]
+    fn linear() {
+        let backoff = Linear {
+            base: Duration::from_millis(500),
+        };
+
+        assert_eq!(backoff.get_backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff.get_backoff(2), Duration::from_millis(1000));
+        assert_eq!(backoff.get_backoff(3), Duration::from_millis(1500));
+    }
+
+    #[test]
+    fn exponential() {
+        let backoff = Exponential {
+            base: Duration::from_millis(500),
+            factor: 3.0,
+        };
+
+        assert_eq!(backoff.get_backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff.get_backoff(2), Duration::from_millis(1500));
+        assert_eq!(backoff.get_backoff(3), Duration::from_millis(4500));
+    }
+
+    #[test]
+    fn binary_exponential() {
+        let backoff = BinaryExponential {
+            base: Duration::from_millis(500),
+        };
+
+        assert_eq!(backoff.get_backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff.get_backoff(2), Duration::from_millis(1000));
+        assert_eq!(backoff.get_backoff(3), Duration::from_millis(2000));
+        assert_eq!(backoff.get_backoff(u32::MAX), Duration::MAX);
+    }
+}
@@ -0,0 +1,235 @@
+pub mod backoff;
+pub mod strategy;
+
+use {
+    crate::error::Error,
+    backoff::BinaryExponential,
+    std::{future::Future, sync::Arc, time::Duration},
+    strategy::{BackoffWithJitter, Limit, RetryOn, Strategy},
+    tonic::Code,
+};
+
+/// Retries failed calls according to a set of strategies.
+#[derive(Clone)]
+pub struct Retrier {
+    strategies: Arc<Vec<Box<dyn Strategy>>>,
+}
+
+impl Retrier {
+    /// Returns a retrier that uses the provided strategies.
+    pub fn new(strategies: Vec<Box<dyn Strategy>>) -> Retrier {
+        Retrier {
+            strategies: Arc::new(strategies),
+        }
+    }
+
+    /// Returns a retrier that never retries.
+    pub fn none() -> Retrier {
+        Retrier::new(vec![Box::new(Limit { max_attempts: 1 })])
+    }
+
+    /// Calls `f` until it succeeds, or until one of the strategies decides that it should not be
+    /// retried, in which case the last error is returned.
+    pub async fn retry<T, E, F, Fut>(&self, mut f: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        E: Into<Error>,
+    {
+        let mut attempts = 0;
+
+        loop {
+            attempts += 1;
+
+            let error = match f().await {
+                Ok(value) => return Ok(value),
+                Err(error) => error.into(),
+            };
+
+            if !self.should_retry(attempts, &error) {
+                return Err(error);
+            }
+
+            let delay = self.delay(attempts);
+            if delay > Duration::from_secs(0) {
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+
+    fn should_retry(&self, attempts: u32, error: &Error) -> bool {
+        self.strategies
+            .iter()
+            .all(|s| s.should_retry(attempts, error))
+    }
+
+    fn delay(&self, attempts: u32) -> Duration {
+        self.strategies
+            .iter()
+            .map(|s| s.delay(attempts))
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+impl Default for Retrier {
+    /// Returns a retrier that makes up to 5 attempts on transient errors, with a binary exponential
+    /// backoff starting at 500ms and capped at 10s.
+    fn default() -> Self {
+        Retrier::new(vec![
+            Box::new(Limit { max_attempts: 5 }),
+            Box::new(RetryOn::new(is_transient)),
+            Box::new(BackoffWithJitter {
+                strategy: Box::new(BinaryExponential {
+                    base: Duration::from_millis(500),
+                }),
+                max_delay: Duration::from_secs(10),
+                jitter: 0.1,
+            }),
+        ])
+    }
+}
+
+/// Returns true if the error is likely to be temporary, such as a network failure or an overloaded
+/// server, and false if retrying the call would produce the same error.
+pub fn is_transient(error: &Error) -> bool {
+    match error {
+        Error::Transport(status) => matches!(
+            status.code(),
+            Code::Unknown
+                | Code::DeadlineExceeded
+                | Code::ResourceExhausted
+                | Code::Aborted
+                | Code::Internal
+                | Code::Unavailable
+        ),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Status;
+
+    #[tokio::test]
+    async fn retry_until_success() {
+        let retrier = Retrier::new(vec![Box::new(Limit { max_attempts: 3 })]);
+
+        let mut calls = 0;
+        let result = retrier
+            .retry(|| {
+                calls += 1;
+                let calls = calls;
+                async move {
+                    if calls < 3 {
+                        Err(Status::unavailable("unavailable"))
+                    } else {
+                        Ok(calls)
+                    }
+                }
+            })
+            .await;
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_until_limit() {
+        let retrier = Retrier::new(vec![Box::new(Limit { max_attempts: 3 })]);
+
+        let mut calls = 0;
+        let result: Result<(), Error> = retrier
+            .retry(|| {
+                calls += 1;
+                async { Err(Status::unavailable("unavailable")) }
+            })
+            .await;
+
+        assert!(matches!(result, Err(Error::Transport(_))));
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn retry_only_transient_errors() {
+        let retrier = Retrier::new(vec![
+            Box::new(Limit { max_attempts: 3 }),
+            Box::new(RetryOn::new(is_transient)),
+        ]);
+
+        let mut calls = 0;
+        let result: Result<(), Error> = retrier
+            .retry(|| {
+                calls += 1;
+                async { Err(Status::invalid_argument("invalid")) }
+            })
+            .await;
+
+        assert!(matches!(result, Err(Error::Transport(_))));
+        assert_eq!(calls, 1);
+
+        let mut calls = 0;
+        let result: Result<(), Error> = retrier
+            .retry(|| {
+                calls += 1;
+                async { Err(Error::AccountExists) }
+            })
+            .await;
+
+        assert!(matches!(result, Err(Error::AccountExists)));
+        assert_eq!(calls, 1);
+    }
+
+    #[tokio::test]
+    async fn none() {
+        let mut calls = 0;
+        let result: Result<(), Error> = Retrier::none()
+            .retry(|| {
+                calls += 1;
+                async { Err(Status::unavailable("unavailable")) }
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(calls, 1);
+    }
+
+    #[test]
+    fn delay_is_longest_of_all_strategies() {
+        let retrier = Retrier::new(vec![
+            Box::new(strategy::Backoff {
+                strategy: Box::new(backoff::Constant {
+                    interval: Duration::from_secs(1),
+                }),
+                max_delay: Duration::from_secs(10),
+            }),
+            Box::new(strategy::Backoff {
+                strategy: Box::new(backoff::Linear {
+                    base: Duration::from_secs(2),
+                }),
+                max_delay: Duration::from_secs(5),
+            }),
+        ]);
+
+        assert_eq!(retrier.delay(1), Duration::from_secs(2));
+        assert_eq!(retrier.delay(2), Duration::from_secs(4));
+        assert_eq!(retrier.delay(3), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let strategy = BackoffWithJitter {
+            strategy: Box::new(backoff::Constant {
+                interval: Duration::from_secs(1),
+            }),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.1,
+        };
+
+        for attempts in 1..100 {
+            let delay = strategy.delay(attempts);
+            assert!(delay >= Duration::from_millis(900));
+            assert!(delay <= Duration::from_millis(1100));
+        }
+    }
+}
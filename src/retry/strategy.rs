@@ -0,0 +1,97 @@
+use {
+    crate::{
+        error::Error,
+        retry::backoff::{duration_from_secs, BackoffStrategy},
+    },
+    rand::Rng,
+    std::time::Duration,
+};
+
+/// Determines whether or not a failed call should be retried, and how long to wait before doing so.
+///
+/// A call is only retried if every strategy agrees that it should be, after waiting for the longest
+/// delay returned by any of the strategies.
+pub trait Strategy: Send + Sync {
+    /// Returns whether or not a call that failed with `error` should be retried after the given number of
+    /// attempts.
+    fn should_retry(&self, attempts: u32, error: &Error) -> bool;
+
+    /// Returns the amount of time to wait before the next attempt.
+    fn delay(&self, _attempts: u32) -> Duration {
+        Duration::from_secs(0)
+    }
+}
+
+/// Limits the total number of attempts, including the first.
+pub struct Limit {
+    pub max_attempts: u32,
+}
+
+impl Strategy for Limit {
+    fn should_retry(&self, attempts: u32, _error: &Error) -> bool {
+        attempts < self.max_attempts
+    }
+}
+
+/// Only retries errors that match a predicate.
+pub struct RetryOn {
+    predicate: Box<dyn Fn(&Error) -> bool + Send + Sync>,
+}
+
+impl RetryOn {
+    pub fn new<F>(predicate: F) -> RetryOn
+    where
+        F: Fn(&Error) -> bool + Send + Sync + 'static,
+    {
+        RetryOn {
+            predicate: Box::new(predicate),
+        }
+    }
+}
+
+impl Strategy for RetryOn {
+    fn should_retry(&self, _attempts: u32, error: &Error) -> bool {
+        (self.predicate)(error)
+    }
+}
+
+/// Waits between attempts, as determined by a backoff strategy, up to `max_delay`.
+pub struct Backoff {
+    pub strategy: Box<dyn BackoffStrategy>,
+    pub max_delay: Duration,
+}
+
+impl Strategy for Backoff {
+    fn should_retry(&self, _attempts: u32, _error: &Error) -> bool {
+        true
+    }
+
+    fn delay(&self, attempts: u32) -> Duration {
+        self.strategy.get_backoff(attempts).min(self.max_delay)
+    }
+}
+
+/// Waits between attempts like `Backoff`, randomly adjusting each delay by up to `jitter` of its value.
+///
+/// For example, a jitter of 0.1 will result in delays between 90% and 110% of the backoff.
+pub struct BackoffWithJitter {
+    pub strategy: Box<dyn BackoffStrategy>,
+    pub max_delay: Duration,
+    pub jitter: f64,
+}
+
+impl Strategy for BackoffWithJitter {
+    fn should_retry(&self, _attempts: u32, _error: &Error) -> bool {
+        true
+    }
+
+    fn delay(&self, attempts: u32) -> Duration {
+        let delay = self.strategy.get_backoff(attempts).min(self.max_delay);
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+
+        let jitter = rand::thread_rng().gen_range(-self.jitter..=self.jitter);
+        duration_from_secs(delay.as_secs_f64() * (1.0 + jitter))
+    }
+}
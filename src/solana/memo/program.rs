@@ -1,4 +1,5 @@
 use {
+    crate::error::Error,
     solana_sdk::{
         instruction::{CompiledInstruction, Instruction},
         pubkey::Pubkey as SolanaPublicKey,
@@ -20,10 +21,10 @@ impl MemoParams {
 pub struct MemoInstruction;
 impl MemoInstruction {
     /// Decode a memo instruction and retrieve the instruction params.
-    pub fn decode_memo(instruction: &CompiledInstruction) -> MemoParams {
-        MemoParams {
-            data: String::from_utf8(instruction.data.clone()).unwrap(),
-        }
+    pub fn decode_memo(instruction: &CompiledInstruction) -> Result<MemoParams, Error> {
+        let data = String::from_utf8(instruction.data.clone()).map_err(|_| Error::InvalidMemo)?;
+
+        Ok(MemoParams { data })
     }
 
     /// Confirms that a given Solana public key is equivalent to the program ID.
@@ -13,11 +13,19 @@ mod instructions {
 
 /// Returns true if the instruction at the given index
 /// is a tranfer instruction, and false otherwise.
+///
+/// Instructions with an out of range program index are not transfers.
 pub fn is_transfer(msg: &Message, instruction_index: usize) -> bool {
     use instructions::transfer;
 
-    let instruction = &msg.instructions[instruction_index];
-    let program_id = msg.program_id(instruction_index).unwrap();
+    let instruction = match msg.instructions.get(instruction_index) {
+        Some(instruction) => instruction,
+        None => return false,
+    };
+    let program_id = match msg.account_keys.get(instruction.program_id_index as usize) {
+        Some(program_id) => program_id,
+        None => return false,
+    };
 
     program_id.eq(&spl_token::id())
         && instruction.accounts.len() == transfer::ACCOUNTS_LEN
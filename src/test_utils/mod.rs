@@ -0,0 +1,4 @@
+//! Utilities for testing code that uses the client without access to Agora.
+
+pub mod server;
+pub mod simulator;
@@ -0,0 +1,501 @@
+use {
+    crate::{
+        client::{builder::ClientBuilder, client::Client, environment::Environment},
+        gen::kin::agora::{
+            account::v4::{self as account_pb, account_server::AccountServer},
+            airdrop::v4::{self as airdrop_pb, airdrop_server::AirdropServer},
+            common::v4 as model_pb_v4,
+            transaction::v4::{self as tx_pb, transaction_server::TransactionServer},
+        },
+        key::{private::PrivateKey, public::PublicKey},
+    },
+    std::{
+        collections::VecDeque,
+        convert::TryFrom,
+        ops::Deref,
+        sync::{Arc, Mutex},
+    },
+    tokio::{
+        net::TcpListener,
+        sync::{mpsc, oneshot},
+    },
+    tokio_stream::wrappers::{TcpListenerStream, UnboundedReceiverStream},
+    tonic::{metadata::MetadataMap, transport::Server, Request, Response, Status},
+};
+
+/// The number of lamports the mock returns for the minimum balance for rent exemption of a token
+/// account.
+pub const MIN_BALANCE_FOR_RENT_EXEMPTION: u64 = 2_039_280;
+
+type Handler<Req, Res> = Box<dyn Fn(&Req) -> Result<Res, Status> + Send + Sync>;
+
+/// The scripted responses and recorded requests of a single RPC.
+///
+/// Scripted responses are returned in the order they were pushed. Once there are none left, the
+/// handler is used to respond. Without a handler, the RPC fails with `Unimplemented`.
+pub struct Rpc<Req, Res> {
+    requests: Mutex<Vec<(MetadataMap, Req)>>,
+    responses: Mutex<VecDeque<Result<Res, Status>>>,
+    handler: Mutex<Option<Handler<Req, Res>>>,
+}
+
+impl<Req: Clone, Res> Rpc<Req, Res> {
+    /// Queues a response, to be returned once all previously queued responses have been returned.
+    pub fn push_response(&self, response: Result<Res, Status>) {
+        self.responses.lock().unwrap().push_back(response);
+    }
+
+    /// Sets the handler used to respond when there are no scripted responses left.
+    pub fn respond_with<F>(&self, handler: F)
+    where
+        F: Fn(&Req) -> Result<Res, Status> + Send + Sync + 'static,
+    {
+        *self.handler.lock().unwrap() = Some(Box::new(handler));
+    }
+
+    /// Returns the requests received so far, in the order they were received.
+    pub fn requests(&self) -> Vec<Req> {
+        let requests = self.requests.lock().unwrap();
+        requests.iter().map(|(_, req)| req.clone()).collect()
+    }
+
+    /// Returns the metadata of the requests received so far, in the order they were received.
+    pub fn metadata(&self) -> Vec<MetadataMap> {
+        let requests = self.requests.lock().unwrap();
+        requests.iter().map(|(md, _)| md.clone()).collect()
+    }
+
+    /// Returns the number of requests received so far.
+    pub fn request_count(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    fn handle(&self, request: Request<Req>) -> Result<Res, Status> {
+        let metadata = request.metadata().clone();
+        let req = request.into_inner();
+
+        let scripted = self.responses.lock().unwrap().pop_front();
+        let res = match scripted {
+            Some(res) => res,
+            None => match &*self.handler.lock().unwrap() {
+                Some(handler) => handler(&req),
+                None => Err(Status::unimplemented("no response scripted")),
+            },
+        };
+
+        self.requests.lock().unwrap().push((metadata, req));
+        res
+    }
+}
+
+impl<Req, Res> Default for Rpc<Req, Res> {
+    fn default() -> Self {
+        Rpc {
+            requests: Mutex::new(Vec::new()),
+            responses: Mutex::new(VecDeque::new()),
+            handler: Mutex::new(None),
+        }
+    }
+}
+
+/// An in-process stand-in for the Agora account, transaction and airdrop services.
+///
+/// The service config, recent blockhash, minimum Kin version and minimum balance for rent exemption
+/// RPCs respond with usable values by default. All other RPCs must be scripted.
+pub struct MockAgora {
+    pub subsidizer: PrivateKey,
+    pub mint: PublicKey,
+
+    pub create_account: Rpc<account_pb::CreateAccountRequest, account_pb::CreateAccountResponse>,
+    pub get_account_info:
+        Rpc<account_pb::GetAccountInfoRequest, account_pb::GetAccountInfoResponse>,
+    pub resolve_token_accounts:
+        Rpc<account_pb::ResolveTokenAccountsRequest, account_pb::ResolveTokenAccountsResponse>,
+    /// Responds with the events sent as soon as a stream is opened. By default, there are none.
+    pub get_events: Rpc<account_pb::GetEventsRequest, Vec<account_pb::Events>>,
+
+    pub get_service_config: Rpc<tx_pb::GetServiceConfigRequest, tx_pb::GetServiceConfigResponse>,
+    pub get_minimum_kin_version:
+        Rpc<tx_pb::GetMinimumKinVersionRequest, tx_pb::GetMinimumKinVersionResponse>,
+    pub get_recent_blockhash:
+        Rpc<tx_pb::GetRecentBlockhashRequest, tx_pb::GetRecentBlockhashResponse>,
+    pub get_minimum_balance_for_rent_exemption: Rpc<
+        tx_pb::GetMinimumBalanceForRentExemptionRequest,
+        tx_pb::GetMinimumBalanceForRentExemptionResponse,
+    >,
+    pub get_history: Rpc<tx_pb::GetHistoryRequest, tx_pb::GetHistoryResponse>,
+    pub sign_transaction: Rpc<tx_pb::SignTransactionRequest, tx_pb::SignTransactionResponse>,
+    pub submit_transaction: Rpc<tx_pb::SubmitTransactionRequest, tx_pb::SubmitTransactionResponse>,
+    pub get_transaction: Rpc<tx_pb::GetTransactionRequest, tx_pb::GetTransactionResponse>,
+
+    pub request_airdrop: Rpc<airdrop_pb::RequestAirdropRequest, airdrop_pb::RequestAirdropResponse>,
+
+    event_streams: Mutex<Vec<EventStream>>,
+}
+
+impl MockAgora {
+    pub fn new() -> MockAgora {
+        let agora = MockAgora {
+            subsidizer: PrivateKey::rand(),
+            mint: PrivateKey::rand().public_key(),
+            create_account: Rpc::default(),
+            get_account_info: Rpc::default(),
+            resolve_token_accounts: Rpc::default(),
+            get_events: Rpc::default(),
+            get_service_config: Rpc::default(),
+            get_minimum_kin_version: Rpc::default(),
+            get_recent_blockhash: Rpc::default(),
+            get_minimum_balance_for_rent_exemption: Rpc::default(),
+            get_history: Rpc::default(),
+            sign_transaction: Rpc::default(),
+            submit_transaction: Rpc::default(),
+            get_transaction: Rpc::default(),
+            request_airdrop: Rpc::default(),
+            event_streams: Mutex::new(Vec::new()),
+        };
+
+        let config = tx_pb::GetServiceConfigResponse {
+            subsidizer_account: Some(account_id(&agora.subsidizer.public_key())),
+            token_program: Some(model_pb_v4::SolanaAccountId {
+                value: spl_token::id().to_bytes().to_vec(),
+            }),
+            token: Some(account_id(&agora.mint)),
+        };
+        agora
+            .get_service_config
+            .respond_with(move |_| Ok(config.clone()));
+
+        // Every request receives a different blockhash.
+        agora.get_recent_blockhash.respond_with(|_| {
+            Ok(tx_pb::GetRecentBlockhashResponse {
+                blockhash: Some(model_pb_v4::Blockhash {
+                    value: rand::random::<[u8; 32]>().to_vec(),
+                }),
+            })
+        });
+
+        agora
+            .get_minimum_kin_version
+            .respond_with(|_| Ok(tx_pb::GetMinimumKinVersionResponse { version: 4 }));
+        agora
+            .get_minimum_balance_for_rent_exemption
+            .respond_with(|_| {
+                Ok(tx_pb::GetMinimumBalanceForRentExemptionResponse {
+                    lamports: MIN_BALANCE_FOR_RENT_EXEMPTION,
+                })
+            });
+        agora.get_events.respond_with(|_| Ok(Vec::new()));
+
+        agora
+    }
+
+    /// Sends events to every open event stream of an account.
+    ///
+    /// An error ends the streams it is sent to.
+    pub fn send_events(&self, account: &PublicKey, events: Result<account_pb::Events, Status>) {
+        let mut streams = self.event_streams.lock().unwrap();
+        streams.retain(|stream| {
+            if stream.account.ne(account) {
+                return true;
+            }
+
+            // Status is not Clone, so errors are copied by their code and message.
+            match &events {
+                Ok(events) => stream.sender.send(Ok(events.clone())).is_ok(),
+                Err(status) => {
+                    let status = Status::new(status.code(), status.message());
+                    stream.sender.send(Err(status)).ok();
+                    false
+                }
+            }
+        });
+    }
+
+    /// Ends every open event stream.
+    pub fn close_event_streams(&self) {
+        self.event_streams.lock().unwrap().clear();
+    }
+
+    /// Returns the accounts with event streams that have not been closed by either side. An account
+    /// is listed once for every open stream.
+    pub fn watched_accounts(&self) -> Vec<PublicKey> {
+        let mut streams = self.event_streams.lock().unwrap();
+        streams.retain(|stream| !stream.sender.is_closed());
+        streams.iter().map(|stream| stream.account).collect()
+    }
+}
+
+impl Default for MockAgora {
+    fn default() -> Self {
+        MockAgora::new()
+    }
+}
+
+/// Returns the proto account id of a public key.
+pub fn account_id(key: &PublicKey) -> model_pb_v4::SolanaAccountId {
+    model_pb_v4::SolanaAccountId {
+        value: key.to_bytes().to_vec(),
+    }
+}
+
+/// A mock Agora server listening on a local port. The server stops when this is dropped.
+pub struct MockServer {
+    agora: Arc<MockAgora>,
+    endpoint: String,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Starts a server with the default mock.
+    pub async fn start() -> MockServer {
+        MockServer::start_with(Arc::new(MockAgora::new())).await
+    }
+
+    /// Starts a server that responds using the given mock.
+    pub async fn start_with(agora: Arc<MockAgora>) -> MockServer {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+
+        let service = Service(agora.clone());
+        let server = Server::builder()
+            .add_service(AccountServer::new(service.clone()))
+            .add_service(TransactionServer::new(service.clone()))
+            .add_service(AirdropServer::new(service))
+            .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                shutdown_rx.await.ok();
+            });
+        tokio::spawn(server);
+
+        MockServer {
+            agora,
+            endpoint,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Returns a builder for a client connected to this server.
+    pub fn client_builder(&self) -> ClientBuilder {
+        ClientBuilder::new(Environment::Test).endpoint(&self.endpoint)
+    }
+
+    /// Returns a client connected to this server.
+    pub fn client(&self) -> Client {
+        self.client_builder().build().unwrap()
+    }
+}
+
+impl Deref for MockServer {
+    type Target = MockAgora;
+
+    fn deref(&self) -> &MockAgora {
+        &self.agora
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
+
+struct EventStream {
+    account: PublicKey,
+    sender: mpsc::UnboundedSender<Result<account_pb::Events, Status>>,
+}
+
+#[derive(Clone)]
+struct Service(Arc<MockAgora>);
+
+#[tonic::async_trait]
+impl account_pb::account_server::Account for Service {
+    async fn create_account(
+        &self,
+        request: Request<account_pb::CreateAccountRequest>,
+    ) -> Result<Response<account_pb::CreateAccountResponse>, Status> {
+        self.0.create_account.handle(request).map(Response::new)
+    }
+
+    async fn get_account_info(
+        &self,
+        request: Request<account_pb::GetAccountInfoRequest>,
+    ) -> Result<Response<account_pb::GetAccountInfoResponse>, Status> {
+        self.0.get_account_info.handle(request).map(Response::new)
+    }
+
+    async fn resolve_token_accounts(
+        &self,
+        request: Request<account_pb::ResolveTokenAccountsRequest>,
+    ) -> Result<Response<account_pb::ResolveTokenAccountsResponse>, Status> {
+        self.0
+            .resolve_token_accounts
+            .handle(request)
+            .map(Response::new)
+    }
+
+    type GetEventsStream = UnboundedReceiverStream<Result<account_pb::Events, Status>>;
+
+    async fn get_events(
+        &self,
+        request: Request<account_pb::GetEventsRequest>,
+    ) -> Result<Response<Self::GetEventsStream>, Status> {
+        let account = match &request.get_ref().account_id {
+            Some(id) => PublicKey::try_from(&id.value)
+                .map_err(|_| Status::invalid_argument("invalid account id"))?,
+            None => return Err(Status::invalid_argument("no account id")),
+        };
+        let initial_events = self.0.get_events.handle(request)?;
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        for events in initial_events {
+            sender.send(Ok(events)).ok();
+        }
+        self.0
+            .event_streams
+            .lock()
+            .unwrap()
+            .push(EventStream { account, sender });
+
+        Ok(Response::new(UnboundedReceiverStream::new(receiver)))
+    }
+}
+
+#[tonic::async_trait]
+impl tx_pb::transaction_server::Transaction for Service {
+    async fn get_service_config(
+        &self,
+        request: Request<tx_pb::GetServiceConfigRequest>,
+    ) -> Result<Response<tx_pb::GetServiceConfigResponse>, Status> {
+        self.0.get_service_config.handle(request).map(Response::new)
+    }
+
+    async fn get_minimum_kin_version(
+        &self,
+        request: Request<tx_pb::GetMinimumKinVersionRequest>,
+    ) -> Result<Response<tx_pb::GetMinimumKinVersionResponse>, Status> {
+        self.0
+            .get_minimum_kin_version
+            .handle(request)
+            .map(Response::new)
+    }
+
+    async fn get_recent_blockhash(
+        &self,
+        request: Request<tx_pb::GetRecentBlockhashRequest>,
+    ) -> Result<Response<tx_pb::GetRecentBlockhashResponse>, Status> {
+        self.0
+            .get_recent_blockhash
+            .handle(request)
+            .map(Response::new)
+    }
+
+    async fn get_minimum_balance_for_rent_exemption(
+        &self,
+        request: Request<tx_pb::GetMinimumBalanceForRentExemptionRequest>,
+    ) -> Result<Response<tx_pb::GetMinimumBalanceForRentExemptionResponse>, Status> {
+        self.0
+            .get_minimum_balance_for_rent_exemption
+            .handle(request)
+            .map(Response::new)
+    }
+
+    async fn get_history(
+        &self,
+        request: Request<tx_pb::GetHistoryRequest>,
+    ) -> Result<Response<tx_pb::GetHistoryResponse>, Status> {
+        self.0.get_history.handle(request).map(Response::new)
+    }
+
+    async fn sign_transaction(
+        &self,
+        request: Request<tx_pb::SignTransactionRequest>,
+    ) -> Result<Response<tx_pb::SignTransactionResponse>, Status> {
+        self.0.sign_transaction.handle(request).map(Response::new)
+    }
+
+    async fn submit_transaction(
+        &self,
+        request: Request<tx_pb::SubmitTransactionRequest>,
+    ) -> Result<Response<tx_pb::SubmitTransactionResponse>, Status> {
+        self.0.submit_transaction.handle(request).map(Response::new)
+    }
+
+    async fn get_transaction(
+        &self,
+        request: Request<tx_pb::GetTransactionRequest>,
+    ) -> Result<Response<tx_pb::GetTransactionResponse>, Status> {
+        self.0.get_transaction.handle(request).map(Response::new)
+    }
+}
+
+#[tonic::async_trait]
+impl airdrop_pb::airdrop_server::Airdrop for Service {
+    async fn request_airdrop(
+        &self,
+        request: Request<airdrop_pb::RequestAirdropRequest>,
+    ) -> Result<Response<airdrop_pb::RequestAirdropResponse>, Status> {
+        self.0.request_airdrop.handle(request).map(Response::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scripted_responses_then_handler() {
+        let server = MockServer::start().await;
+        let client = server.client();
+
+        server
+            .get_minimum_kin_version
+            .push_response(Ok(tx_pb::GetMinimumKinVersionResponse { version: 3 }));
+
+        let first = client.internal.tx.get_minimum_kin_version().await.unwrap();
+        let second = client.internal.tx.get_minimum_kin_version().await.unwrap();
+        assert_eq!(first.version, 3);
+        assert_eq!(second.version, 4);
+        assert_eq!(server.get_minimum_kin_version.request_count(), 2);
+    }
+
+    #[tokio::test]
+    async fn unscripted_rpc_is_unimplemented() {
+        let server = MockServer::start().await;
+        let client = server.client();
+
+        let result = client
+            .internal
+            .airdrop
+            .request_airdrop(
+                &PrivateKey::rand().public_key(),
+                1,
+                crate::solana::commitment::Commitment::Single,
+            )
+            .await;
+        match result {
+            Err(crate::error::Error::Transport(status)) => {
+                assert_eq!(status.code(), tonic::Code::Unimplemented)
+            }
+            _ => panic!("expected an unimplemented error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn records_requests_and_metadata() {
+        let server = MockServer::start().await;
+        let client = server.client_builder().app_index(7).build().unwrap();
+
+        client.internal.tx.get_service_config().await.unwrap();
+
+        let metadata = server.get_service_config.metadata();
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].get("app-index").unwrap(), "7");
+        assert_eq!(metadata[0].get("kin-version").unwrap(), "4");
+    }
+}
@@ -0,0 +1,900 @@
+use {
+    crate::{
+        client::{builder::ClientBuilder, client::Client},
+        gen::kin::agora::{
+            account::v4 as account_pb,
+            airdrop::v4 as airdrop_pb,
+            common::{v3 as model_pb_v3, v4 as model_pb_v4},
+            transaction::v4 as tx_pb,
+        },
+        key::{private::PrivateKey, public::PublicKey},
+        solana::{memo::program::MemoProgram, token::program::ACCOUNT_LEN},
+        test_utils::server::{account_id, MockAgora, MockServer},
+    },
+    model_pb_v4::transaction_error::Reason,
+    solana_sdk::{
+        hash::Hash,
+        program_option::COption,
+        pubkey::Pubkey as SolanaPublicKey,
+        signature::{Keypair as SolanaKeypair, Signature, Signer},
+        system_instruction::SystemInstruction,
+        system_program,
+        transaction::Transaction as SolanaTransaction,
+    },
+    spl_associated_token_account::get_associated_token_address,
+    spl_token::instruction::{AuthorityType, TokenInstruction},
+    std::{
+        collections::{HashMap, HashSet},
+        convert::{TryFrom, TryInto},
+        ops::Deref,
+        sync::{Arc, Mutex},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    tonic::Status,
+};
+
+/// The maximum number of items returned by a single history request.
+pub const DEFAULT_HISTORY_PAGE_SIZE: usize = 100;
+
+/// A token account tracked by the simulator.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenAccount {
+    pub owner: SolanaPublicKey,
+    pub close_authority: Option<SolanaPublicKey>,
+    pub balance: u64,
+
+    /// The order in which accounts were created, used to order resolved accounts.
+    created: u64,
+}
+
+/// The reason an instruction could not be applied to the ledger.
+enum Failure {
+    /// An account that is created by the transaction already exists.
+    AccountExists,
+    Instruction(usize, Reason),
+}
+
+#[derive(Default)]
+struct Ledger {
+    accounts: HashMap<SolanaPublicKey, TokenAccount>,
+    accounts_created: u64,
+
+    blockhashes: HashSet<Hash>,
+    history: Vec<tx_pb::HistoryItem>,
+    dedupe_ids: HashMap<Vec<u8>, Vec<u8>>,
+    history_page_size: usize,
+}
+
+/// An in-memory stand-in for Agora that keeps a ledger of Kin token accounts.
+///
+/// Transactions submitted to the simulator are checked and applied to the ledger in the same way
+/// they would be by Agora and Solana. The simulator supports the instructions built by the client:
+/// system account creation, associated token account creation, and the spl-token initialize
+/// account, transfer, set authority and close account instructions.
+///
+/// Every RPC can still be scripted through the underlying `MockServer`, in which case the scripted
+/// responses take precedence and do not affect the ledger.
+pub struct Simulator {
+    server: MockServer,
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+impl Simulator {
+    pub async fn start() -> Simulator {
+        let agora = Arc::new(MockAgora::new());
+        let ledger = Arc::new(Mutex::new(Ledger {
+            history_page_size: DEFAULT_HISTORY_PAGE_SIZE,
+            ..Ledger::default()
+        }));
+
+        install_handlers(&agora, &ledger);
+
+        Simulator {
+            server: MockServer::start_with(agora).await,
+            ledger,
+        }
+    }
+
+    /// Returns a builder for a client connected to the simulator.
+    pub fn client_builder(&self) -> ClientBuilder {
+        self.server.client_builder()
+    }
+
+    /// Returns a client connected to the simulator.
+    pub fn client(&self) -> Client {
+        self.server.client()
+    }
+
+    /// Returns a token account, if it exists.
+    pub fn token_account(&self, account: &PublicKey) -> Option<TokenAccount> {
+        let ledger = self.ledger.lock().unwrap();
+        ledger.accounts.get(&account.to_solana_key()).cloned()
+    }
+
+    /// Returns the balance of a token account, if it exists.
+    pub fn balance(&self, account: &PublicKey) -> Option<u64> {
+        self.token_account(account).map(|account| account.balance)
+    }
+
+    /// Returns the token accounts owned by an account, with the associated token account first.
+    pub fn token_accounts(&self, owner: &PublicKey) -> Vec<PublicKey> {
+        let ledger = self.ledger.lock().unwrap();
+        ledger
+            .resolve(&owner.to_solana_key(), &self.server.mint.to_solana_key())
+            .iter()
+            .map(|(key, _)| PublicKey::new(key.as_ref()))
+            .collect()
+    }
+
+    /// Creates a token account that is not an associated token account, with the subsidizer as
+    /// its close authority, and returns its address.
+    pub fn create_token_account(&self, owner: &PublicKey, balance: u64) -> PublicKey {
+        let account = PrivateKey::rand().public_key();
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.insert_account(
+            account.to_solana_key(),
+            owner.to_solana_key(),
+            Some(self.server.subsidizer.public_key().to_solana_key()),
+        );
+        ledger.credit(&account.to_solana_key(), balance);
+
+        account
+    }
+
+    /// Creates the associated token account of an owner, with the given close authority, and returns
+    /// its address.
+    pub fn create_associated_token_account(
+        &self,
+        owner: &PublicKey,
+        close_authority: Option<&PublicKey>,
+    ) -> PublicKey {
+        let account =
+            get_associated_token_address(&owner.to_solana_key(), &self.server.mint.to_solana_key());
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.insert_account(
+            account,
+            owner.to_solana_key(),
+            close_authority.map(|key| key.to_solana_key()),
+        );
+
+        PublicKey::new(account.as_ref())
+    }
+
+    /// Adds quarks to a token account, without recording a transaction.
+    pub fn mint(&self, account: &PublicKey, quarks: u64) {
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.credit(&account.to_solana_key(), quarks);
+    }
+
+    /// Forgets every blockhash issued so far, so that transactions signed with them fail with a bad
+    /// nonce.
+    pub fn expire_blockhashes(&self) {
+        self.ledger.lock().unwrap().blockhashes.clear();
+    }
+
+    /// Returns every transaction applied to the ledger, oldest first.
+    pub fn history(&self) -> Vec<tx_pb::HistoryItem> {
+        self.ledger.lock().unwrap().history.clone()
+    }
+
+    /// Sets the maximum number of items returned by a single history request.
+    pub fn set_history_page_size(&self, size: usize) {
+        self.ledger.lock().unwrap().history_page_size = size;
+    }
+}
+
+impl Deref for Simulator {
+    type Target = MockServer;
+
+    fn deref(&self) -> &MockServer {
+        &self.server
+    }
+}
+
+fn install_handlers(agora: &Arc<MockAgora>, ledger: &Arc<Mutex<Ledger>>) {
+    let subsidizer = agora.subsidizer;
+    let mint = agora.mint.to_solana_key();
+
+    // The handlers are owned by the mock, so they only hold a weak reference to it.
+    let weak_agora = Arc::downgrade(agora);
+
+    let l = ledger.clone();
+    agora.get_recent_blockhash.respond_with(move |_| {
+        let blockhash = Hash::new(&rand::random::<[u8; 32]>());
+        l.lock().unwrap().blockhashes.insert(blockhash);
+
+        Ok(tx_pb::GetRecentBlockhashResponse {
+            blockhash: Some(model_pb_v4::Blockhash {
+                value: blockhash.as_ref().to_vec(),
+            }),
+        })
+    });
+
+    let (l, a) = (ledger.clone(), weak_agora.clone());
+    agora.create_account.respond_with(move |req| {
+        use account_pb::create_account_response::Result;
+
+        let mut tx = decode_tx(req.transaction.as_ref())?;
+        if !cosign(&mut tx, &subsidizer) {
+            return Ok(account_pb::CreateAccountResponse {
+                result: Result::PayerRequired as i32,
+                account_info: None,
+            });
+        }
+
+        let mut ledger = l.lock().unwrap();
+        if !ledger.blockhashes.contains(&tx.message.recent_blockhash) {
+            return Ok(account_pb::CreateAccountResponse {
+                result: Result::BadNonce as i32,
+                account_info: None,
+            });
+        }
+        if tx.verify().is_err() {
+            return Err(Status::unauthenticated("invalid signature"));
+        }
+
+        let created = match ledger.apply(&tx, &mint) {
+            Ok(created) => created,
+            Err(Failure::AccountExists) => {
+                return Ok(account_pb::CreateAccountResponse {
+                    result: Result::Exists as i32,
+                    account_info: None,
+                })
+            }
+            Err(Failure::Instruction(_, reason)) => {
+                return Err(Status::invalid_argument(format!(
+                    "create account transaction failed: {:?}",
+                    reason
+                )))
+            }
+        };
+        ledger.record(&tx, None);
+        if let Some(agora) = a.upgrade() {
+            ledger.notify(&agora, &tx, &mint);
+        }
+
+        Ok(account_pb::CreateAccountResponse {
+            result: Result::Ok as i32,
+            account_info: created.first().and_then(|key| ledger.account_info(key)),
+        })
+    });
+
+    let l = ledger.clone();
+    agora.get_account_info.respond_with(move |req| {
+        use account_pb::get_account_info_response::Result;
+
+        let key = decode_key(req.account_id.as_ref())?;
+        let info = l.lock().unwrap().account_info(&key);
+
+        Ok(account_pb::GetAccountInfoResponse {
+            result: match info {
+                Some(_) => Result::Ok as i32,
+                None => Result::NotFound as i32,
+            },
+            account_info: info,
+        })
+    });
+
+    let l = ledger.clone();
+    agora.get_events.respond_with(move |req| {
+        let account = decode_key(req.account_id.as_ref())?;
+        let ledger = l.lock().unwrap();
+
+        // Agora only sends events for accounts that exist.
+        if ledger.exists(&account, &mint) {
+            return Ok(Vec::new());
+        }
+
+        Ok(vec![account_pb::Events {
+            result: account_pb::events::Result::NotFound as i32,
+            events: Vec::new(),
+        }])
+    });
+
+    let l = ledger.clone();
+    agora.resolve_token_accounts.respond_with(move |req| {
+        let owner = decode_key(req.account_id.as_ref())?;
+        let ledger = l.lock().unwrap();
+
+        let token_account_infos = ledger
+            .resolve(&owner, &mint)
+            .iter()
+            .map(|(key, account)| {
+                if req.include_account_info {
+                    account.to_proto(key)
+                } else {
+                    account_pb::AccountInfo {
+                        account_id: Some(solana_account_id(key)),
+                        ..account_pb::AccountInfo::default()
+                    }
+                }
+            })
+            .collect();
+
+        Ok(account_pb::ResolveTokenAccountsResponse {
+            token_account_infos,
+            ..account_pb::ResolveTokenAccountsResponse::default()
+        })
+    });
+
+    agora.sign_transaction.respond_with(move |req| {
+        use tx_pb::sign_transaction_response::Result;
+
+        let mut tx = decode_tx(req.transaction.as_ref())?;
+        if !cosign(&mut tx, &subsidizer) {
+            return Ok(tx_pb::SignTransactionResponse {
+                result: Result::Rejected as i32,
+                signature: None,
+                invoice_errors: Vec::new(),
+            });
+        }
+
+        Ok(tx_pb::SignTransactionResponse {
+            result: Result::Ok as i32,
+            signature: Some(signature(&tx)),
+            invoice_errors: Vec::new(),
+        })
+    });
+
+    let (l, a) = (ledger.clone(), weak_agora);
+    agora.submit_transaction.respond_with(move |req| {
+        use tx_pb::submit_transaction_response::Result;
+
+        let tx = decode_tx(req.transaction.as_ref())?;
+        let mut res = tx_pb::SubmitTransactionResponse {
+            result: Result::Ok as i32,
+            signature: Some(signature(&tx)),
+            transaction_error: None,
+            invoice_errors: Vec::new(),
+        };
+
+        let mut ledger = l.lock().unwrap();
+        if let Some(id) = ledger.dedupe_ids.get(&req.dedupe_id) {
+            res.signature = Some(model_pb_v4::TransactionSignature { value: id.clone() });
+            return Ok(res);
+        }
+        if tx.signatures[0] == Signature::default() {
+            res.result = Result::PayerRequired as i32;
+            return Ok(res);
+        }
+        if tx.verify().is_err() {
+            res.result = Result::Failed as i32;
+            res.transaction_error = Some(transaction_error(-1, Reason::Unauthorized));
+            return Ok(res);
+        }
+        if !ledger.blockhashes.contains(&tx.message.recent_blockhash) {
+            res.result = Result::Failed as i32;
+            res.transaction_error = Some(transaction_error(-1, Reason::BadNonce));
+            return Ok(res);
+        }
+        if ledger.transaction(tx.signatures[0].as_ref()).is_some() {
+            res.result = Result::AlreadySubmitted as i32;
+            return Ok(res);
+        }
+
+        match ledger.apply(&tx, &mint) {
+            Ok(_) => (),
+            Err(Failure::AccountExists) => {
+                res.result = Result::Failed as i32;
+                res.transaction_error = Some(transaction_error(-1, Reason::Unknown));
+                return Ok(res);
+            }
+            Err(Failure::Instruction(index, reason)) => {
+                res.result = Result::Failed as i32;
+                res.transaction_error = Some(transaction_error(index as i32, reason));
+                return Ok(res);
+            }
+        }
+
+        ledger.record(&tx, req.invoice_list.clone());
+        if let Some(agora) = a.upgrade() {
+            ledger.notify(&agora, &tx, &mint);
+        }
+        if !req.dedupe_id.is_empty() {
+            let id = tx.signatures[0].as_ref().to_vec();
+            ledger.dedupe_ids.insert(req.dedupe_id.clone(), id);
+        }
+
+        Ok(res)
+    });
+
+    let l = ledger.clone();
+    agora.get_transaction.respond_with(move |req| {
+        use tx_pb::get_transaction_response::State;
+
+        let id = req
+            .transaction_id
+            .as_ref()
+            .ok_or_else(|| Status::invalid_argument("no transaction id"))?;
+        let item = l.lock().unwrap().transaction(&id.value).cloned();
+
+        Ok(tx_pb::GetTransactionResponse {
+            state: match item {
+                Some(_) => State::Success as i32,
+                None => State::Unknown as i32,
+            },
+            slot: 0,
+            confirmations: 0,
+            item,
+        })
+    });
+
+    let l = ledger.clone();
+    agora.get_history.respond_with(move |req| {
+        use tx_pb::{get_history_request::Direction, get_history_response::Result};
+
+        let account = decode_key(req.account_id.as_ref())?;
+        let start = match &req.cursor {
+            Some(cursor) => decode_cursor(cursor)? + 1,
+            None => 0,
+        };
+
+        let ledger = l.lock().unwrap();
+        let mut items: Vec<&tx_pb::HistoryItem> = ledger
+            .history
+            .iter()
+            .filter(|item| involves(item, &account))
+            .collect();
+        if req.direction == Direction::Desc as i32 {
+            items.reverse();
+        }
+
+        // The cursor is the position of the item in the account's history, in the requested
+        // direction.
+        let items: Vec<tx_pb::HistoryItem> = items
+            .into_iter()
+            .enumerate()
+            .skip(start)
+            .take(ledger.history_page_size)
+            .map(|(i, item)| tx_pb::HistoryItem {
+                cursor: Some(tx_pb::Cursor {
+                    value: (i as u64).to_be_bytes().to_vec(),
+                }),
+                ..item.clone()
+            })
+            .collect();
+
+        Ok(tx_pb::GetHistoryResponse {
+            result: match items.is_empty() && start == 0 && !ledger.exists(&account, &mint) {
+                true => Result::NotFound as i32,
+                false => Result::Ok as i32,
+            },
+            items,
+        })
+    });
+
+    let l = ledger.clone();
+    agora.request_airdrop.respond_with(move |req| {
+        use airdrop_pb::request_airdrop_response::Result;
+
+        let key = decode_key(req.account_id.as_ref())?;
+        let mut ledger = l.lock().unwrap();
+        if !ledger.accounts.contains_key(&key) {
+            return Ok(airdrop_pb::RequestAirdropResponse {
+                result: Result::NotFound as i32,
+                signature: None,
+            });
+        }
+        ledger.credit(&key, req.quarks);
+
+        Ok(airdrop_pb::RequestAirdropResponse {
+            result: Result::Ok as i32,
+            signature: Some(model_pb_v4::TransactionSignature {
+                value: (0..64).map(|_| rand::random::<u8>()).collect(),
+            }),
+        })
+    });
+}
+
+impl Ledger {
+    fn insert_account(
+        &mut self,
+        key: SolanaPublicKey,
+        owner: SolanaPublicKey,
+        close_authority: Option<SolanaPublicKey>,
+    ) {
+        self.accounts_created += 1;
+        self.accounts.insert(
+            key,
+            TokenAccount {
+                owner,
+                close_authority,
+                balance: 0,
+                created: self.accounts_created,
+            },
+        );
+    }
+
+    fn credit(&mut self, key: &SolanaPublicKey, quarks: u64) {
+        let account = self.accounts.get_mut(key).expect("token account not found");
+        account.balance += quarks;
+    }
+
+    fn account_info(&self, key: &SolanaPublicKey) -> Option<account_pb::AccountInfo> {
+        self.accounts.get(key).map(|account| account.to_proto(key))
+    }
+
+    /// Returns the token accounts of an owner, with the associated token account first, followed by
+    /// the rest in order of creation.
+    fn resolve(
+        &self,
+        owner: &SolanaPublicKey,
+        mint: &SolanaPublicKey,
+    ) -> Vec<(SolanaPublicKey, TokenAccount)> {
+        let assoc = get_associated_token_address(owner, mint);
+
+        let mut accounts: Vec<(SolanaPublicKey, TokenAccount)> = self
+            .accounts
+            .iter()
+            .filter(|(_, account)| account.owner.eq(owner))
+            .map(|(key, account)| (*key, account.clone()))
+            .collect();
+        accounts.sort_by_key(|(key, account)| (key.ne(&assoc), account.created));
+
+        accounts
+    }
+
+    /// Returns whether an account is a token account, or owns any.
+    fn exists(&self, key: &SolanaPublicKey, mint: &SolanaPublicKey) -> bool {
+        self.accounts.contains_key(key) || !self.resolve(key, mint).is_empty()
+    }
+
+    fn transaction(&self, id: &[u8]) -> Option<&tx_pb::HistoryItem> {
+        self.history.iter().find(|item| match &item.transaction_id {
+            Some(tx_id) => tx_id.value == id,
+            None => false,
+        })
+    }
+
+    /// Applies every instruction of a transaction, or none of them if any fails. Returns the token
+    /// accounts created by the transaction.
+    fn apply(
+        &mut self,
+        tx: &SolanaTransaction,
+        mint: &SolanaPublicKey,
+    ) -> Result<Vec<SolanaPublicKey>, Failure> {
+        let accounts = self.accounts.clone();
+        let accounts_created = self.accounts_created;
+
+        let mut allocated = HashSet::new();
+        let mut created = Vec::new();
+        for i in 0..tx.message.instructions.len() {
+            if let Err(failure) = self.apply_instruction(tx, i, mint, &mut allocated, &mut created)
+            {
+                self.accounts = accounts;
+                self.accounts_created = accounts_created;
+                return Err(failure);
+            }
+        }
+
+        Ok(created)
+    }
+
+    fn apply_instruction(
+        &mut self,
+        tx: &SolanaTransaction,
+        index: usize,
+        mint: &SolanaPublicKey,
+        allocated: &mut HashSet<SolanaPublicKey>,
+        created: &mut Vec<SolanaPublicKey>,
+    ) -> Result<(), Failure> {
+        let fail = |reason| Failure::Instruction(index, reason);
+
+        let message = &tx.message;
+        let instruction = &message.instructions[index];
+        let program_id = message.account_keys[instruction.program_id_index as usize];
+
+        let account = |i: usize| -> Result<SolanaPublicKey, Failure> {
+            match instruction.accounts.get(i) {
+                Some(key_index) => Ok(message.account_keys[*key_index as usize]),
+                None => Err(fail(Reason::Unknown)),
+            }
+        };
+        let is_signer = |key: &SolanaPublicKey| -> bool {
+            let position = message.account_keys.iter().position(|k| k.eq(key));
+            matches!(position, Some(i) if message.is_signer(i))
+        };
+
+        if program_id == MemoProgram::id() {
+            return Ok(());
+        }
+
+        if program_id == system_program::id() {
+            return match bincode::deserialize(&instruction.data) {
+                Ok(SystemInstruction::CreateAccount { space, owner, .. })
+                    if space == ACCOUNT_LEN && owner == spl_token::id() =>
+                {
+                    let key = account(1)?;
+                    if self.accounts.contains_key(&key) || !allocated.insert(key) {
+                        return Err(Failure::AccountExists);
+                    }
+                    Ok(())
+                }
+                _ => Err(fail(Reason::Unknown)),
+            };
+        }
+
+        if program_id == spl_associated_token_account::id() {
+            let (assoc, wallet) = (account(1)?, account(2)?);
+            if account(3)?.ne(mint) || assoc.ne(&get_associated_token_address(&wallet, mint)) {
+                return Err(fail(Reason::InvalidAccount));
+            }
+            if self.accounts.contains_key(&assoc) {
+                return Err(Failure::AccountExists);
+            }
+
+            self.insert_account(assoc, wallet, None);
+            created.push(assoc);
+            return Ok(());
+        }
+
+        if program_id != spl_token::id() {
+            return Err(fail(Reason::Unknown));
+        }
+
+        let token_instruction =
+            TokenInstruction::unpack(&instruction.data).map_err(|_| fail(Reason::Unknown))?;
+        match token_instruction {
+            TokenInstruction::InitializeAccount => {
+                let (key, owner) = (account(0)?, account(2)?);
+                if account(1)?.ne(mint) || !allocated.remove(&key) {
+                    return Err(fail(Reason::InvalidAccount));
+                }
+
+                self.insert_account(key, owner, None);
+                created.push(key);
+            }
+            TokenInstruction::Transfer { amount } => {
+                let (source, dest, authority) = (account(0)?, account(1)?, account(2)?);
+                if !self.accounts.contains_key(&dest) {
+                    return Err(fail(Reason::InvalidAccount));
+                }
+
+                let source = self
+                    .accounts
+                    .get_mut(&source)
+                    .ok_or_else(|| fail(Reason::InvalidAccount))?;
+                if source.owner.ne(&authority) || !is_signer(&authority) {
+                    return Err(fail(Reason::Unauthorized));
+                }
+                if source.balance < amount {
+                    return Err(fail(Reason::InsufficientFunds));
+                }
+                source.balance -= amount;
+
+                self.credit(&dest, amount);
+            }
+            TokenInstruction::SetAuthority {
+                authority_type,
+                new_authority,
+            } => {
+                let (key, authority) = (account(0)?, account(1)?);
+                let token_account = self
+                    .accounts
+                    .get_mut(&key)
+                    .ok_or_else(|| fail(Reason::InvalidAccount))?;
+                let current = match authority_type {
+                    AuthorityType::AccountOwner => token_account.owner,
+                    AuthorityType::CloseAccount => {
+                        token_account.close_authority.unwrap_or(token_account.owner)
+                    }
+                    _ => return Err(fail(Reason::Unknown)),
+                };
+                if current.ne(&authority) || !is_signer(&authority) {
+                    return Err(fail(Reason::Unauthorized));
+                }
+
+                match (authority_type, new_authority) {
+                    (AuthorityType::AccountOwner, COption::Some(owner)) => {
+                        token_account.owner = owner
+                    }
+                    (AuthorityType::CloseAccount, new_authority) => {
+                        token_account.close_authority = new_authority.into()
+                    }
+                    _ => return Err(fail(Reason::Unknown)),
+                }
+            }
+            TokenInstruction::CloseAccount => {
+                let (key, authority) = (account(0)?, account(2)?);
+                let token_account = self
+                    .accounts
+                    .get(&key)
+                    .ok_or_else(|| fail(Reason::InvalidAccount))?;
+                let close_authority = token_account.close_authority.unwrap_or(token_account.owner);
+                if close_authority.ne(&authority) || !is_signer(&authority) {
+                    return Err(fail(Reason::Unauthorized));
+                }
+                if token_account.balance > 0 {
+                    return Err(fail(Reason::Unknown));
+                }
+
+                self.accounts.remove(&key);
+            }
+            _ => return Err(fail(Reason::Unknown)),
+        }
+
+        Ok(())
+    }
+
+    /// Sends an applied transaction to the event streams of the accounts involved in it, along with
+    /// the updated account info of watched token accounts.
+    fn notify(&self, agora: &MockAgora, tx: &SolanaTransaction, mint: &SolanaPublicKey) {
+        let mut accounts: Vec<PublicKey> = Vec::new();
+        for account in agora.watched_accounts() {
+            if !accounts.contains(&account) {
+                accounts.push(account);
+            }
+        }
+
+        for account in accounts {
+            let key = account.to_solana_key();
+            let involved = tx.message.account_keys.contains(&key)
+                || self
+                    .resolve(&key, mint)
+                    .iter()
+                    .any(|(token_account, _)| tx.message.account_keys.contains(token_account));
+            if !involved {
+                continue;
+            }
+
+            let mut events = vec![account_pb::Event {
+                r#type: Some(account_pb::event::Type::TransactionEvent(
+                    account_pb::TransactionEvent {
+                        transaction: Some(model_pb_v4::Transaction {
+                            value: bincode::serialize(tx).unwrap(),
+                        }),
+                        transaction_error: None,
+                    },
+                )),
+            }];
+            if let Some(info) = self.account_info(&key) {
+                events.push(account_pb::Event {
+                    r#type: Some(account_pb::event::Type::AccountUpdateEvent(
+                        account_pb::AccountUpdateEvent {
+                            account_info: Some(info),
+                        },
+                    )),
+                });
+            }
+
+            let events = account_pb::Events {
+                result: account_pb::events::Result::Ok as i32,
+                events,
+            };
+            agora.send_events(&account, Ok(events));
+        }
+    }
+
+    /// Adds an applied transaction to the history.
+    fn record(&mut self, tx: &SolanaTransaction, invoice_list: Option<model_pb_v3::InvoiceList>) {
+        let mut payments = Vec::new();
+        for (index, instruction) in tx.message.instructions.iter().enumerate() {
+            let program_id = tx.message.account_keys[instruction.program_id_index as usize];
+            if program_id != spl_token::id() {
+                continue;
+            }
+
+            if let Ok(TokenInstruction::Transfer { amount }) =
+                TokenInstruction::unpack(&instruction.data)
+            {
+                let key = |i: usize| tx.message.account_keys[instruction.accounts[i] as usize];
+                payments.push(tx_pb::history_item::Payment {
+                    source: Some(solana_account_id(&key(0))),
+                    destination: Some(solana_account_id(&key(1))),
+                    amount: amount as i64,
+                    index: index as u32,
+                });
+            }
+        }
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        self.history.push(tx_pb::HistoryItem {
+            transaction_id: Some(model_pb_v4::TransactionId {
+                value: tx.signatures[0].as_ref().to_vec(),
+            }),
+            cursor: None,
+            transaction_error: None,
+            payments,
+            invoice_list,
+            transaction_time: Some(prost_types::Timestamp {
+                seconds: now.as_secs() as i64,
+                nanos: now.subsec_nanos() as i32,
+            }),
+            raw_transaction: Some(tx_pb::history_item::RawTransaction::SolanaTransaction(
+                model_pb_v4::Transaction {
+                    value: bincode::serialize(tx).unwrap(),
+                },
+            )),
+        });
+    }
+}
+
+impl TokenAccount {
+    fn to_proto(&self, key: &SolanaPublicKey) -> account_pb::AccountInfo {
+        account_pb::AccountInfo {
+            account_id: Some(solana_account_id(key)),
+            balance: self.balance as i64,
+            owner: Some(solana_account_id(&self.owner)),
+            close_authority: self.close_authority.as_ref().map(solana_account_id),
+        }
+    }
+}
+
+/// Signs a transaction as the subsidizer, if the subsidizer is its fee payer and has not already
+/// signed it. Returns false if the subsidizer is not the fee payer.
+fn cosign(tx: &mut SolanaTransaction, subsidizer: &PrivateKey) -> bool {
+    let subsidizer_key = subsidizer.public_key().to_solana_key();
+    if tx.message.account_keys[0].ne(&subsidizer_key) {
+        return tx.signatures[0] != Signature::default();
+    }
+
+    let keypair = SolanaKeypair::from_bytes(&subsidizer.secret_key()).unwrap();
+    tx.signatures[0] = keypair.sign_message(&tx.message_data());
+    true
+}
+
+/// Returns whether an account is a party to any of the payments of a transaction, or signed it.
+fn involves(item: &tx_pb::HistoryItem, account: &SolanaPublicKey) -> bool {
+    let id = solana_account_id(account);
+    let in_payments = item
+        .payments
+        .iter()
+        .any(|p| p.source.as_ref() == Some(&id) || p.destination.as_ref() == Some(&id));
+
+    let in_tx = match &item.raw_transaction {
+        Some(tx_pb::history_item::RawTransaction::SolanaTransaction(raw)) => {
+            let tx: SolanaTransaction = bincode::deserialize(&raw.value).unwrap();
+            tx.message.account_keys.contains(account)
+        }
+        _ => false,
+    };
+
+    in_payments || in_tx
+}
+
+fn decode_tx(tx: Option<&model_pb_v4::Transaction>) -> Result<SolanaTransaction, Status> {
+    let tx = tx.ok_or_else(|| Status::invalid_argument("no transaction"))?;
+    let tx: SolanaTransaction = bincode::deserialize(&tx.value)
+        .map_err(|_| Status::invalid_argument("invalid transaction"))?;
+    if tx.signatures.is_empty() {
+        return Err(Status::invalid_argument("transaction has no signatures"));
+    }
+
+    Ok(tx)
+}
+
+fn decode_key(id: Option<&model_pb_v4::SolanaAccountId>) -> Result<SolanaPublicKey, Status> {
+    let id = id.ok_or_else(|| Status::invalid_argument("no account id"))?;
+    let key = PublicKey::try_from(&id.value)
+        .map_err(|_| Status::invalid_argument("invalid account id"))?;
+
+    Ok(key.to_solana_key())
+}
+
+fn decode_cursor(cursor: &tx_pb::Cursor) -> Result<usize, Status> {
+    let bytes: [u8; 8] = cursor
+        .value
+        .as_slice()
+        .try_into()
+        .map_err(|_| Status::invalid_argument("invalid cursor"))?;
+
+    Ok(u64::from_be_bytes(bytes) as usize)
+}
+
+fn signature(tx: &SolanaTransaction) -> model_pb_v4::TransactionSignature {
+    model_pb_v4::TransactionSignature {
+        value: tx.signatures[0].as_ref().to_vec(),
+    }
+}
+
+fn transaction_error(instruction_index: i32, reason: Reason) -> model_pb_v4::TransactionError {
+    model_pb_v4::TransactionError {
+        reason: reason as i32,
+        instruction_index,
+        raw: Vec::new(),
+    }
+}
+
+fn solana_account_id(key: &SolanaPublicKey) -> model_pb_v4::SolanaAccountId {
+    account_id(&PublicKey::new(key.as_ref()))
+}
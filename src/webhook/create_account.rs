@@ -0,0 +1,278 @@
+use {
+    crate::{
+        error::Error, key::public::PublicKey, model::memo::Memo,
+        solana::memo::program::MemoProgram, webhook::decode_solana_tx,
+    },
+    serde::{Deserialize, Serialize},
+    solana_sdk::{
+        instruction::CompiledInstruction, pubkey::Pubkey,
+        transaction::Transaction as SolanaTransaction,
+    },
+    spl_token::instruction::{AuthorityType, TokenInstruction},
+};
+
+/// A request from Agora to approve the creation of an account before it is subsidized by the app.
+///
+/// The transaction creates the associated token account of the owner, and sets its close
+/// authority, optionally after an Agora memo.
+#[derive(Debug)]
+pub struct CreateAccountRequest {
+    pub kin_version: u32,
+    pub transaction: SolanaTransaction,
+    pub memo: Option<Memo>,
+
+    /// The account that funds the creation.
+    pub funder: PublicKey,
+    pub owner: PublicKey,
+    pub mint: PublicKey,
+
+    /// The associated token account being created.
+    pub token_account: PublicKey,
+    pub close_authority: Option<PublicKey>,
+}
+
+impl CreateAccountRequest {
+    /// Parses the body of a create account webhook request.
+    pub fn from_json(body: &[u8]) -> Result<CreateAccountRequest, Error> {
+        let req: CreateAccountRequestJson = serde_json::from_slice(body)?;
+        let transaction = decode_solana_tx(&req.solana_transaction)?;
+
+        let message = &transaction.message;
+        let mut instructions = message.instructions.iter().peekable();
+
+        let mut memo = None;
+        if let Some(instruction) = instructions.peek() {
+            if program_id(&transaction, instruction)? == MemoProgram::id() {
+                let data = String::from_utf8(instruction.data.clone())
+                    .map_err(|_| Error::InvalidWebhookPayload("invalid memo"))?;
+                memo = Memo::from_base64(&data, false).ok();
+                instructions.next();
+            }
+        }
+
+        let create = instructions
+            .next()
+            .ok_or(Error::InvalidWebhookPayload("missing create instruction"))?;
+        if program_id(&transaction, create)? != spl_associated_token_account::id() {
+            return Err(Error::InvalidWebhookPayload("invalid create instruction"));
+        }
+        let funder = account(&transaction, create, 0)?;
+        let token_account = account(&transaction, create, 1)?;
+        let owner = account(&transaction, create, 2)?;
+        let mint = account(&transaction, create, 3)?;
+
+        let set_auth = instructions.next().ok_or(Error::InvalidWebhookPayload(
+            "missing close authority instruction",
+        ))?;
+        if program_id(&transaction, set_auth)? != spl_token::id() {
+            return Err(Error::InvalidWebhookPayload(
+                "invalid close authority instruction",
+            ));
+        }
+        let close_authority = match TokenInstruction::unpack(&set_auth.data) {
+            Ok(TokenInstruction::SetAuthority {
+                authority_type: AuthorityType::CloseAccount,
+                new_authority,
+            }) if account(&transaction, set_auth, 0)? == token_account => {
+                Option::<Pubkey>::from(new_authority).map(|key| PublicKey::new(key.as_ref()))
+            }
+            _ => {
+                return Err(Error::InvalidWebhookPayload(
+                    "invalid close authority instruction",
+                ))
+            }
+        };
+
+        if instructions.next().is_some() {
+            return Err(Error::InvalidWebhookPayload("unexpected instruction"));
+        }
+
+        Ok(CreateAccountRequest {
+            kin_version: req.kin_version,
+            memo,
+            funder,
+            owner,
+            mint,
+            token_account,
+            close_authority,
+            transaction,
+        })
+    }
+}
+
+/// The response to a create account webhook request.
+#[derive(Debug, Clone, Default)]
+pub struct CreateAccountResponse {
+    rejected: bool,
+    reason: Option<String>,
+}
+
+impl CreateAccountResponse {
+    /// Returns a response that approves the creation.
+    pub fn approve() -> CreateAccountResponse {
+        CreateAccountResponse::default()
+    }
+
+    /// Rejects the creation.
+    pub fn reject(&mut self) {
+        self.rejected = true;
+    }
+
+    /// Rejects the creation, with a reason that is returned to Agora.
+    pub fn reject_with_reason(&mut self, reason: &str) {
+        self.rejected = true;
+        self.reason = Some(reason.to_string());
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.rejected
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// Returns the HTTP status code of the response.
+    pub fn status_code(&self) -> u16 {
+        if self.rejected {
+            403
+        } else {
+            200
+        }
+    }
+
+    /// Returns the JSON body of the response.
+    pub fn to_json(&self) -> Vec<u8> {
+        serde_json::to_vec(&CreateAccountResponseJson {
+            reason: self.reason.as_deref(),
+        })
+        .unwrap()
+    }
+}
+
+fn program_id(tx: &SolanaTransaction, instruction: &CompiledInstruction) -> Result<Pubkey, Error> {
+    tx.message
+        .account_keys
+        .get(instruction.program_id_index as usize)
+        .copied()
+        .ok_or(Error::InvalidWebhookPayload("invalid program index"))
+}
+
+fn account(
+    tx: &SolanaTransaction,
+    instruction: &CompiledInstruction,
+    index: usize,
+) -> Result<PublicKey, Error> {
+    instruction
+        .accounts
+        .get(index)
+        .and_then(|i| tx.message.account_keys.get(*i as usize))
+        .map(|key| PublicKey::new(key.as_ref()))
+        .ok_or(Error::InvalidWebhookPayload("invalid account index"))
+}
+
+#[derive(Deserialize)]
+struct CreateAccountRequestJson {
+    #[serde(default)]
+    kin_version: u32,
+    solana_transaction: String,
+}
+
+#[derive(Serialize)]
+struct CreateAccountResponseJson<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            key::private::PrivateKey,
+            model::transaction_type::TransactionType,
+            solana::{
+                memo::program::MemoParams,
+                token::instruction::{
+                    create_assoc_account_and_set_close_auth, set_owner_authority,
+                },
+            },
+        },
+        serde_json::{json, Value},
+        spl_associated_token_account::get_associated_token_address,
+    };
+
+    fn request_body(tx: &SolanaTransaction) -> Vec<u8> {
+        json!({
+            "kin_version": 4,
+            "solana_transaction": base64::encode(bincode::serialize(tx).unwrap()),
+        })
+        .to_string()
+        .into_bytes()
+    }
+
+    #[test]
+    fn parse_request() {
+        let funder = PrivateKey::rand().public_key();
+        let owner = PrivateKey::rand().public_key();
+        let mint = PrivateKey::rand().public_key();
+        let assoc = get_associated_token_address(&owner.to_solana_key(), &mint.to_solana_key());
+
+        let memo = Memo::new(1, TransactionType::None, 7, &[0; 29]);
+        let mut instructions = vec![MemoProgram::memo(MemoParams::new(memo.to_base64()))];
+        instructions.append(&mut create_assoc_account_and_set_close_auth(
+            &funder.to_solana_key(),
+            &owner.to_solana_key(),
+            &mint.to_solana_key(),
+            &assoc,
+        ));
+        let tx = SolanaTransaction::new_with_payer(&instructions, Some(&funder.to_solana_key()));
+
+        let req = CreateAccountRequest::from_json(&request_body(&tx)).unwrap();
+        assert_eq!(req.kin_version, 4);
+        assert_eq!(req.memo.unwrap().app_index(), 7);
+        assert_eq!(req.funder, funder);
+        assert_eq!(req.owner, owner);
+        assert_eq!(req.mint, mint);
+        assert_eq!(req.token_account.to_solana_key(), assoc);
+        assert_eq!(req.close_authority, Some(funder));
+    }
+
+    #[test]
+    fn parse_invalid_request() {
+        let funder = PrivateKey::rand().public_key().to_solana_key();
+        let owner = PrivateKey::rand().public_key().to_solana_key();
+        let mint = PrivateKey::rand().public_key().to_solana_key();
+        let assoc = get_associated_token_address(&owner, &mint);
+
+        // The owner of the account cannot be changed.
+        let mut instructions =
+            create_assoc_account_and_set_close_auth(&funder, &owner, &mint, &assoc);
+        instructions.push(set_owner_authority(&assoc, &funder, &owner));
+        let tx = SolanaTransaction::new_with_payer(&instructions, Some(&funder));
+
+        assert!(matches!(
+            CreateAccountRequest::from_json(&request_body(&tx)),
+            Err(Error::InvalidWebhookPayload(_))
+        ));
+    }
+
+    #[test]
+    fn responses() {
+        let resp = CreateAccountResponse::approve();
+        assert_eq!(resp.status_code(), 200);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&resp.to_json()).unwrap(),
+            json!({})
+        );
+
+        let mut resp = CreateAccountResponse::approve();
+        resp.reject_with_reason("too many accounts");
+        assert!(resp.is_rejected());
+        assert_eq!(resp.status_code(), 403);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&resp.to_json()).unwrap(),
+            json!({"reason": "too many accounts"})
+        );
+    }
+}
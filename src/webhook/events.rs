@@ -0,0 +1,309 @@
+use {
+    crate::{
+        error::Error,
+        gen::kin::agora::common::v4 as model_pb_v4,
+        model::{
+            invoice::InvoiceList,
+            transaction::{TransactionData, TransactionState},
+            transaction_id::TransactionId,
+        },
+        webhook::{
+            decode_base64, decode_invoice_list, decode_solana_tx, set_invoices, solana_tx_data,
+        },
+    },
+    serde::Deserialize,
+    solana_sdk::transaction::Transaction as SolanaTransaction,
+};
+
+/// An event delivered by the events webhook.
+///
+/// Events of kinds that are not known to this SDK have no fields set.
+#[derive(Debug)]
+pub struct Event {
+    pub transaction_event: Option<TransactionEvent>,
+}
+
+/// A transaction that has been submitted to the blockchain.
+#[derive(Debug)]
+pub struct TransactionEvent {
+    pub kin_version: u32,
+    pub tx_id: TransactionId,
+    pub invoice_list: Option<InvoiceList>,
+    pub solana_event: Option<SolanaEvent>,
+}
+
+/// The Solana details of a transaction event.
+#[derive(Debug)]
+pub struct SolanaEvent {
+    pub transaction: SolanaTransaction,
+
+    /// The reason the transaction failed, if it did.
+    pub transaction_error: Option<String>,
+
+    /// The raw Solana error the transaction failed with, if it did.
+    pub transaction_error_raw: Option<String>,
+}
+
+impl TransactionEvent {
+    /// Returns the data of the transaction, with an invoice for each payment if the transaction has
+    /// an invoice list.
+    ///
+    /// Returns None if the event has no Solana transaction.
+    pub fn transaction_data(&self) -> Result<Option<TransactionData>, Error> {
+        let event = match &self.solana_event {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+
+        let tx_error = event.transaction_error.as_deref().and_then(proto_tx_error);
+        let mut data = solana_tx_data(&event.transaction, tx_error.as_ref())?;
+
+        // Errors without a known reason still mean that the transaction failed.
+        if event.transaction_error.is_some() {
+            data.tx_state = TransactionState::Failed;
+        }
+
+        set_invoices(&mut data.payments, self.invoice_list.as_ref())?;
+
+        Ok(Some(data))
+    }
+}
+
+/// Parses the body of an events webhook request.
+pub fn parse_events(body: &[u8]) -> Result<Vec<Event>, Error> {
+    let events: Vec<EventJson> = serde_json::from_slice(body)?;
+
+    events.into_iter().map(Event::from_json).collect()
+}
+
+impl Event {
+    fn from_json(event: EventJson) -> Result<Event, Error> {
+        let transaction_event = match event.transaction_event {
+            Some(event) => Some(TransactionEvent::from_json(event)?),
+            None => None,
+        };
+
+        Ok(Event { transaction_event })
+    }
+}
+
+impl TransactionEvent {
+    fn from_json(event: TransactionEventJson) -> Result<TransactionEvent, Error> {
+        let tx_id = TransactionId::from(decode_base64(&event.tx_id, "invalid tx_id")?);
+
+        let invoice_list = match &event.invoice_list {
+            Some(invoice_list) => Some(decode_invoice_list(invoice_list)?),
+            None => None,
+        };
+
+        let solana_event = match event.solana_event {
+            Some(solana_event) => Some(SolanaEvent {
+                transaction: decode_solana_tx(&solana_event.transaction)?,
+                transaction_error: solana_event.transaction_error.filter(|e| !e.is_empty()),
+                transaction_error_raw: solana_event.transaction_error_raw.filter(|e| !e.is_empty()),
+            }),
+            None => None,
+        };
+
+        Ok(TransactionEvent {
+            kin_version: event.kin_version,
+            tx_id,
+            invoice_list,
+            solana_event,
+        })
+    }
+}
+
+/// Returns the proto error for a transaction error reason, or None if the reason is not known.
+fn proto_tx_error(reason: &str) -> Option<model_pb_v4::TransactionError> {
+    use model_pb_v4::transaction_error::Reason;
+
+    let reason = match reason {
+        "unauthorized" => Reason::Unauthorized,
+        "bad_nonce" => Reason::BadNonce,
+        "insufficient_funds" => Reason::InsufficientFunds,
+        "invalid_account" => Reason::InvalidAccount,
+        _ => return None,
+    };
+
+    // The failed instruction is not included in events.
+    Some(model_pb_v4::TransactionError {
+        reason: reason as i32,
+        instruction_index: -1,
+        raw: Vec::new(),
+    })
+}
+
+#[derive(Deserialize)]
+struct EventJson {
+    transaction_event: Option<TransactionEventJson>,
+}
+
+#[derive(Deserialize)]
+struct TransactionEventJson {
+    #[serde(default)]
+    kin_version: u32,
+    tx_id: String,
+    invoice_list: Option<String>,
+    solana_event: Option<SolanaEventJson>,
+}
+
+#[derive(Deserialize)]
+struct SolanaEventJson {
+    transaction: String,
+    transaction_error: Option<String>,
+    transaction_error_raw: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            error::TransactionError,
+            key::private::PrivateKey,
+            model::{invoice::Invoice, transaction_type::TransactionType},
+            solana::memo::program::{MemoParams, MemoProgram},
+        },
+        prost::Message,
+        serde_json::json,
+    };
+
+    fn transfer_tx(amounts: &[u64]) -> SolanaTransaction {
+        let owner = PrivateKey::rand().public_key().to_solana_key();
+        let source = PrivateKey::rand().public_key().to_solana_key();
+
+        let mut instructions = vec![MemoProgram::memo(MemoParams::new("memo".to_string()))];
+        for amount in amounts {
+            let dest = PrivateKey::rand().public_key().to_solana_key();
+            instructions.push(
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &source,
+                    &dest,
+                    &owner,
+                    &[],
+                    *amount,
+                )
+                .unwrap(),
+            );
+        }
+
+        let mut tx = SolanaTransaction::new_with_payer(&instructions, Some(&owner));
+        tx.signatures[0] = solana_sdk::signature::Signature::new(&[3; 64]);
+        tx
+    }
+
+    fn encode_tx(tx: &SolanaTransaction) -> String {
+        base64::encode(bincode::serialize(tx).unwrap())
+    }
+
+    #[test]
+    fn parse_transaction_event() {
+        let tx = transfer_tx(&[10, 20]);
+        let invoice_list = InvoiceList::new(&[
+            Invoice::new("first", 10, None, None),
+            Invoice::new("second", 20, None, Some(b"sku")),
+        ]);
+
+        let body = json!([{
+            "transaction_event": {
+                "kin_version": 4,
+                "tx_id": base64::encode([3; 64]),
+                "invoice_list": base64::encode(invoice_list.to_proto().encode_to_vec()),
+                "solana_event": {
+                    "transaction": encode_tx(&tx),
+                },
+            },
+        }]);
+
+        let events = parse_events(body.to_string().as_bytes()).unwrap();
+        assert_eq!(events.len(), 1);
+
+        let event = events[0].transaction_event.as_ref().unwrap();
+        assert_eq!(event.kin_version, 4);
+        assert_eq!(event.tx_id, TransactionId::new(&[3; 64]));
+
+        let data = event.transaction_data().unwrap().unwrap();
+        assert_eq!(data.tx_id, event.tx_id);
+        assert_eq!(data.tx_state, TransactionState::Success);
+        assert_eq!(data.payments.len(), 2);
+        assert_eq!(data.payments[1].quarks, 20);
+        assert_eq!(data.payments[1].tx_type, TransactionType::Unknown);
+        let invoice = data.payments[1].invoice.as_ref().unwrap();
+        assert_eq!(invoice.items[0].title, "second");
+        assert_eq!(invoice.items[0].sku, Some(b"sku".to_vec()));
+    }
+
+    #[test]
+    fn parse_failed_transaction_event() {
+        let tx = transfer_tx(&[10]);
+        let body = json!([
+            {
+                "transaction_event": {
+                    "tx_id": base64::encode([3; 64]),
+                    "solana_event": {
+                        "transaction": encode_tx(&tx),
+                        "transaction_error": "bad_nonce",
+                        "transaction_error_raw": "BlockhashNotFound",
+                    },
+                },
+            },
+            {},
+        ]);
+
+        let events = parse_events(body.to_string().as_bytes()).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events[1].transaction_event.is_none());
+
+        let event = events[0].transaction_event.as_ref().unwrap();
+        let solana_event = event.solana_event.as_ref().unwrap();
+        assert_eq!(
+            solana_event.transaction_error_raw.as_deref(),
+            Some("BlockhashNotFound")
+        );
+
+        let data = event.transaction_data().unwrap().unwrap();
+        assert_eq!(data.tx_state, TransactionState::Failed);
+        assert!(matches!(
+            data.errors.unwrap().tx_error,
+            Some(TransactionError::BadNonce(_))
+        ));
+    }
+
+    #[test]
+    fn parse_event_with_malformed_transaction() {
+        let mut tx = transfer_tx(&[10]);
+        tx.message.instructions[1].program_id_index = tx.message.account_keys.len() as u8;
+
+        let body = json!([{
+            "transaction_event": {
+                "tx_id": base64::encode([3; 64]),
+                "solana_event": {"transaction": encode_tx(&tx)},
+            },
+        }]);
+
+        let events = parse_events(body.to_string().as_bytes()).unwrap();
+        let event = events[0].transaction_event.as_ref().unwrap();
+        assert!(matches!(
+            event.transaction_data(),
+            Err(Error::InvalidWebhookPayload(_))
+        ));
+    }
+
+    #[test]
+    fn parse_invalid_events() {
+        assert!(matches!(parse_events(b"{"), Err(Error::WebhookJson(_))));
+
+        let body = json!([{
+            "transaction_event": {
+                "tx_id": base64::encode([3; 64]),
+                "solana_event": {"transaction": "not a transaction"},
+            },
+        }]);
+        assert!(matches!(
+            parse_events(body.to_string().as_bytes()),
+            Err(Error::InvalidWebhookPayload(_))
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+//! Types for handling the webhooks that Agora calls on app servers.
+//!
+//! Request bodies are parsed without any assumptions about the HTTP framework in use.
+//! The `webhook-server` feature adds a hyper service that routes requests to handlers.
+
+pub mod create_account;
+pub mod events;
+pub mod sign_transaction;
+pub mod signature;
+
+#[cfg(feature = "webhook-server")]
+pub mod server;
+
+use {
+    crate::{
+        error::Error,
+        gen::kin::agora::common::v3 as model_pb_v3,
+        gen::kin::agora::common::v4 as model_pb_v4,
+        model::{invoice::InvoiceList, payment::ReadOnlyPayment, transaction::TransactionData},
+    },
+    prost::Message,
+    solana_sdk::transaction::Transaction as SolanaTransaction,
+};
+
+/// Decodes a base64 field of a webhook payload.
+fn decode_base64(value: &str, field: &'static str) -> Result<Vec<u8>, Error> {
+    base64::decode(value).map_err(|_| Error::InvalidWebhookPayload(field))
+}
+
+/// Decodes a base64-encoded Solana transaction from a webhook payload.
+fn decode_solana_tx(value: &str) -> Result<SolanaTransaction, Error> {
+    let bytes = decode_base64(value, "invalid transaction encoding")?;
+
+    bincode::deserialize(&bytes)
+        .map_err(|_| Error::InvalidWebhookPayload("invalid Solana transaction"))
+}
+
+/// Returns the data of a Solana transaction from a webhook payload.
+///
+/// The transaction comes from an end user, so it is parsed as untrusted input.
+fn solana_tx_data(
+    tx: &SolanaTransaction,
+    tx_error: Option<&model_pb_v4::TransactionError>,
+) -> Result<TransactionData, Error> {
+    TransactionData::from_solana_tx(tx, tx_error).map_err(|e| match e {
+        Error::MalformedResponse(reason) => Error::InvalidWebhookPayload(reason),
+        e => e,
+    })
+}
+
+/// Decodes a base64-encoded invoice list proto from a webhook payload.
+fn decode_invoice_list(value: &str) -> Result<InvoiceList, Error> {
+    let bytes = decode_base64(value, "invalid invoice list encoding")?;
+    let proto = model_pb_v3::InvoiceList::decode(bytes.as_slice())
+        .map_err(|_| Error::InvalidWebhookPayload("invalid invoice list"))?;
+
+    Ok(InvoiceList::from_proto(proto))
+}
+
+/// Sets the invoice of each payment from the invoice list of its transaction, if there is one.
+fn set_invoices(
+    payments: &mut [ReadOnlyPayment],
+    invoice_list: Option<&InvoiceList>,
+) -> Result<(), Error> {
+    let invoice_list = match invoice_list {
+        Some(invoice_list) => invoice_list,
+        None => return Ok(()),
+    };
+
+    if invoice_list.invoices.len() != payments.len() {
+        return Err(Error::InvalidWebhookPayload(
+            "number of invoices does not match number of payments",
+        ));
+    }
+
+    for (payment, invoice) in payments.iter_mut().zip(&invoice_list.invoices) {
+        payment.invoice = Some(invoice.clone());
+        payment.memo = None;
+    }
+
+    Ok(())
+}
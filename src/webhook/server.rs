@@ -0,0 +1,484 @@
+use {
+    crate::webhook::{
+        create_account::{CreateAccountRequest, CreateAccountResponse},
+        events::{parse_events, Event},
+        sign_transaction::{SignTransactionRequest, SignTransactionResponse},
+        signature::{SignatureVerifier, HMAC_HEADER},
+    },
+    async_trait::async_trait,
+    hyper::{
+        body::HttpBody,
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        service::{make_service_fn, Service},
+        Body, Method, Request, Response, Server, StatusCode,
+    },
+    std::{
+        convert::Infallible,
+        future::Future,
+        net::SocketAddr,
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+    },
+};
+
+pub const EVENTS_PATH: &str = "/events";
+pub const SIGN_TRANSACTION_PATH: &str = "/sign_transaction";
+pub const CREATE_ACCOUNT_PATH: &str = "/create_account";
+
+/// The default maximum size of a webhook request body, in bytes.
+pub const DEFAULT_MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// An error returned by a webhook handler, which results in an internal server error response.
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync>;
+
+#[async_trait]
+pub trait EventsHandler: Send + Sync {
+    async fn handle_events(&self, events: Vec<Event>) -> Result<(), HandlerError>;
+}
+
+#[async_trait]
+pub trait SignTransactionHandler: Send + Sync {
+    async fn sign_transaction(
+        &self,
+        req: SignTransactionRequest,
+    ) -> Result<SignTransactionResponse, HandlerError>;
+}
+
+#[async_trait]
+pub trait CreateAccountHandler: Send + Sync {
+    async fn create_account(
+        &self,
+        req: CreateAccountRequest,
+    ) -> Result<CreateAccountResponse, HandlerError>;
+}
+
+/// A hyper service that serves the webhooks of an app.
+///
+/// Requests are verified against the webhook secret before they are parsed and passed to their
+/// handler. Webhooks without a handler respond with 404, and requests with a body larger than the
+/// maximum body size respond with 413 without being verified.
+#[derive(Clone)]
+pub struct WebhookServer {
+    verifier: SignatureVerifier,
+    max_body_size: usize,
+    events: Option<Arc<dyn EventsHandler>>,
+    sign_transaction: Option<Arc<dyn SignTransactionHandler>>,
+    create_account: Option<Arc<dyn CreateAccountHandler>>,
+}
+
+impl WebhookServer {
+    pub fn new(secret: &[u8]) -> WebhookServer {
+        WebhookServer {
+            verifier: SignatureVerifier::new(secret),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            events: None,
+            sign_transaction: None,
+            create_account: None,
+        }
+    }
+
+    /// Sets the maximum size of a request body, in bytes. Defaults to `DEFAULT_MAX_BODY_SIZE`.
+    pub fn max_body_size(mut self, size: usize) -> WebhookServer {
+        self.max_body_size = size;
+        self
+    }
+
+    pub fn events<H: EventsHandler + 'static>(mut self, handler: H) -> WebhookServer {
+        self.events = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn sign_transaction<H: SignTransactionHandler + 'static>(
+        mut self,
+        handler: H,
+    ) -> WebhookServer {
+        self.sign_transaction = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn create_account<H: CreateAccountHandler + 'static>(
+        mut self,
+        handler: H,
+    ) -> WebhookServer {
+        self.create_account = Some(Arc::new(handler));
+        self
+    }
+
+    /// Serves the webhooks on an address until the server fails.
+    pub async fn serve(self, addr: &SocketAddr) -> Result<(), hyper::Error> {
+        let make_service = make_service_fn(move |_| {
+            let server = self.clone();
+            async move { Ok::<_, Infallible>(server) }
+        });
+
+        Server::bind(addr).serve(make_service).await
+    }
+
+    /// Handles a webhook request.
+    pub async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if req.method() != Method::POST {
+            return status_response(StatusCode::METHOD_NOT_ALLOWED);
+        }
+
+        let path = req.uri().path().to_string();
+        let routed = match path.as_str() {
+            EVENTS_PATH => self.events.is_some(),
+            SIGN_TRANSACTION_PATH => self.sign_transaction.is_some(),
+            CREATE_ACCOUNT_PATH => self.create_account.is_some(),
+            _ => false,
+        };
+        if !routed {
+            return status_response(StatusCode::NOT_FOUND);
+        }
+
+        let content_length = req
+            .headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+        if matches!(content_length, Some(len) if len > self.max_body_size as u64) {
+            return status_response(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        let signature = req
+            .headers()
+            .get(HMAC_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let body = match read_body(req.into_body(), self.max_body_size).await {
+            Ok(body) => body,
+            Err(status) => return status_response(status),
+        };
+        if self.verifier.verify(signature.as_deref(), &body).is_err() {
+            return status_response(StatusCode::UNAUTHORIZED);
+        }
+
+        match path.as_str() {
+            EVENTS_PATH => self.handle_events(&body).await,
+            SIGN_TRANSACTION_PATH => self.handle_sign_transaction(&body).await,
+            _ => self.handle_create_account(&body).await,
+        }
+    }
+
+    async fn handle_events(&self, body: &[u8]) -> Response<Body> {
+        let events = match parse_events(body) {
+            Ok(events) => events,
+            Err(_) => return status_response(StatusCode::BAD_REQUEST),
+        };
+
+        match self.events.as_ref().unwrap().handle_events(events).await {
+            Ok(()) => status_response(StatusCode::OK),
+            Err(_) => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    async fn handle_sign_transaction(&self, body: &[u8]) -> Response<Body> {
+        let req = match SignTransactionRequest::from_json(body) {
+            Ok(req) => req,
+            Err(_) => return status_response(StatusCode::BAD_REQUEST),
+        };
+
+        let handler = self.sign_transaction.as_ref().unwrap();
+        match handler.sign_transaction(req).await {
+            Ok(resp) => json_response(resp.status_code(), resp.to_json()),
+            Err(_) => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+
+    async fn handle_create_account(&self, body: &[u8]) -> Response<Body> {
+        let req = match CreateAccountRequest::from_json(body) {
+            Ok(req) => req,
+            Err(_) => return status_response(StatusCode::BAD_REQUEST),
+        };
+
+        let handler = self.create_account.as_ref().unwrap();
+        match handler.create_account(req).await {
+            Ok(resp) => json_response(resp.status_code(), resp.to_json()),
+            Err(_) => status_response(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+}
+
+impl Service<Request<Body>> for WebhookServer {
+    type Response = Response<Body>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Response<Body>, Infallible>> + Send>>;
+
+    fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let server = self.clone();
+        Box::pin(async move { Ok(server.handle(req).await) })
+    }
+}
+
+/// Reads a request body, failing with 413 as soon as it is larger than `max_size` bytes.
+async fn read_body(mut body: Body, max_size: usize) -> Result<Vec<u8>, StatusCode> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if bytes.len() + chunk.len() > max_size {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut resp = Response::new(Body::empty());
+    *resp.status_mut() = status;
+    resp
+}
+
+fn json_response(status: u16, body: Vec<u8>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            key::private::PrivateKey,
+            solana::token::instruction::create_assoc_account_and_set_close_auth,
+        },
+        serde_json::{json, Value},
+        solana_sdk::transaction::Transaction as SolanaTransaction,
+        spl_associated_token_account::get_associated_token_address,
+        std::{sync::Mutex, time::Duration},
+        tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            net::TcpStream,
+        },
+    };
+
+    const SECRET: &[u8] = b"secret";
+
+    struct Handler {
+        events: Arc<Mutex<Vec<Event>>>,
+    }
+
+    #[async_trait]
+    impl EventsHandler for Handler {
+        async fn handle_events(&self, events: Vec<Event>) -> Result<(), HandlerError> {
+            self.events.lock().unwrap().extend(events);
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl SignTransactionHandler for Handler {
+        async fn sign_transaction(
+            &self,
+            req: SignTransactionRequest,
+        ) -> Result<SignTransactionResponse, HandlerError> {
+            let mut resp = SignTransactionResponse::approve();
+            if req.payments.iter().any(|payment| payment.quarks > 100) {
+                resp.mark_wrong_destination(0);
+            }
+            Ok(resp)
+        }
+    }
+
+    #[async_trait]
+    impl CreateAccountHandler for Handler {
+        async fn create_account(
+            &self,
+            _: CreateAccountRequest,
+        ) -> Result<CreateAccountResponse, HandlerError> {
+            Err("unavailable".into())
+        }
+    }
+
+    fn server() -> (WebhookServer, Arc<Mutex<Vec<Event>>>) {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let server = WebhookServer::new(SECRET)
+            .events(Handler {
+                events: events.clone(),
+            })
+            .sign_transaction(Handler {
+                events: events.clone(),
+            })
+            .create_account(Handler {
+                events: events.clone(),
+            });
+
+        (server, events)
+    }
+
+    fn request(path: &str, body: &Value, signature: Option<String>) -> Request<Body> {
+        let body = body.to_string();
+        let signature =
+            signature.unwrap_or_else(|| SignatureVerifier::new(SECRET).sign(body.as_bytes()));
+
+        Request::post(path)
+            .header(HMAC_HEADER, signature)
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn response_json(resp: Response<Body>) -> Value {
+        let body = hyper::body::to_bytes(resp.into_body()).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    fn transfer_tx(quarks: u64) -> String {
+        let owner = PrivateKey::rand().public_key().to_solana_key();
+        let dest = PrivateKey::rand().public_key().to_solana_key();
+        let instruction =
+            spl_token::instruction::transfer(&spl_token::id(), &owner, &dest, &owner, &[], quarks)
+                .unwrap();
+
+        let tx = SolanaTransaction::new_with_payer(&[instruction], Some(&owner));
+        base64::encode(bincode::serialize(&tx).unwrap())
+    }
+
+    #[tokio::test]
+    async fn events() {
+        let (server, events) = server();
+
+        let body = json!([{
+            "transaction_event": {
+                "tx_id": base64::encode([3; 64]),
+                "solana_event": {"transaction": transfer_tx(10)},
+            },
+        }]);
+        let resp = server.handle(request(EVENTS_PATH, &body, None)).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        // Requests that are not signed with the secret are not handled.
+        let resp = server
+            .handle(request(EVENTS_PATH, &body, Some(base64::encode([0; 32]))))
+            .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(events.lock().unwrap().len(), 1);
+
+        let resp = server
+            .handle(request(EVENTS_PATH, &json!({"events": []}), None))
+            .await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn sign_transaction() {
+        let (server, _) = server();
+
+        let body = json!({"kin_version": 4, "solana_transaction": transfer_tx(10)});
+        let resp = server
+            .handle(request(SIGN_TRANSACTION_PATH, &body, None))
+            .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers()[CONTENT_TYPE], "application/json");
+        assert_eq!(response_json(resp).await, json!({}));
+
+        let body = json!({"kin_version": 4, "solana_transaction": transfer_tx(1000)});
+        let resp = server
+            .handle(request(SIGN_TRANSACTION_PATH, &body, None))
+            .await;
+        assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        assert_eq!(
+            response_json(resp).await,
+            json!({"invoice_errors": [{"operation_index": 0, "reason": "wrong_destination"}]})
+        );
+    }
+
+    #[tokio::test]
+    async fn oversized_request() {
+        let (server, events) = server();
+        let server = server.max_body_size(64);
+
+        // Requests are rejected from their content length, before their body is read.
+        let mut req = request(EVENTS_PATH, &json!([]), None);
+        req.headers_mut()
+            .insert(CONTENT_LENGTH, (1024 * 1024).to_string().parse().unwrap());
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Bodies without a content length are capped while they are read.
+        let (mut sender, stream_body) = Body::channel();
+        let req = Request::post(EVENTS_PATH).body(stream_body).unwrap();
+        tokio::spawn(async move {
+            for _ in 0..10 {
+                if sender.send_data(vec![b' '; 16].into()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(events.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn routing() {
+        let (server, _) = server();
+
+        let resp = server.handle(request("/unknown", &json!({}), None)).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let resp = WebhookServer::new(SECRET)
+            .handle(request(EVENTS_PATH, &json!([]), None))
+            .await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = Request::get(EVENTS_PATH).body(Body::empty()).unwrap();
+        let resp = server.handle(req).await;
+        assert_eq!(resp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let (server, _) = server();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+        tokio::spawn(async move { server.serve(&addr).await });
+
+        let funder = PrivateKey::rand().public_key().to_solana_key();
+        let owner = PrivateKey::rand().public_key().to_solana_key();
+        let mint = PrivateKey::rand().public_key().to_solana_key();
+        let assoc = get_associated_token_address(&owner, &mint);
+        let tx = SolanaTransaction::new_with_payer(
+            &create_assoc_account_and_set_close_auth(&funder, &owner, &mint, &assoc),
+            Some(&funder),
+        );
+
+        // Handler errors respond with an internal server error.
+        let body = json!({"solana_transaction": base64::encode(bincode::serialize(&tx).unwrap())})
+            .to_string();
+        let req = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\n{}: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            CREATE_ACCOUNT_PATH,
+            HMAC_HEADER,
+            SignatureVerifier::new(SECRET).sign(body.as_bytes()),
+            body.len(),
+            body
+        );
+
+        let mut stream = None;
+        for _ in 0..50 {
+            if let Ok(s) = TcpStream::connect(addr).await {
+                stream = Some(s);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut stream = stream.unwrap();
+
+        let mut resp = String::new();
+        stream.write_all(req.as_bytes()).await.unwrap();
+        stream.read_to_string(&mut resp).await.unwrap();
+        assert!(resp.starts_with("HTTP/1.1 500"), "{}", resp);
+    }
+}
@@ -0,0 +1,349 @@
+use {
+    crate::{
+        error::Error,
+        gen::kin::agora::common::v3 as model_pb_v3,
+        model::{invoice::InvoiceList, payment::ReadOnlyPayment, transaction_id::TransactionId},
+        webhook::{decode_invoice_list, decode_solana_tx, set_invoices, solana_tx_data},
+    },
+    serde::{Deserialize, Serialize},
+    solana_sdk::transaction::Transaction as SolanaTransaction,
+};
+
+/// A request from Agora to approve a transaction before it is signed by the app's subsidizer.
+#[derive(Debug)]
+pub struct SignTransactionRequest {
+    pub kin_version: u32,
+    pub transaction: SolanaTransaction,
+    pub invoice_list: Option<InvoiceList>,
+
+    /// The payments of the transaction, with their invoices if the transaction has an invoice list.
+    pub payments: Vec<ReadOnlyPayment>,
+}
+
+impl SignTransactionRequest {
+    /// Parses the body of a sign transaction webhook request.
+    pub fn from_json(body: &[u8]) -> Result<SignTransactionRequest, Error> {
+        let req: SignTransactionRequestJson = serde_json::from_slice(body)?;
+
+        let transaction = decode_solana_tx(&req.solana_transaction)?;
+        let invoice_list = match &req.invoice_list {
+            Some(invoice_list) => Some(decode_invoice_list(invoice_list)?),
+            None => None,
+        };
+
+        let mut payments = solana_tx_data(&transaction, None)?.payments;
+        set_invoices(&mut payments, invoice_list.as_ref())?;
+
+        Ok(SignTransactionRequest {
+            kin_version: req.kin_version,
+            transaction,
+            invoice_list,
+            payments,
+        })
+    }
+
+    /// Returns the id the transaction will have once it is signed.
+    pub fn tx_id(&self) -> Option<TransactionId> {
+        self.transaction
+            .signatures
+            .first()
+            .map(|signature| TransactionId::from(*signature))
+    }
+}
+
+/// Reasons an invoice in a sign transaction request can be rejected for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvoiceErrorReason {
+    AlreadyPaid,
+    WrongDestination,
+    SkuNotFound,
+}
+
+impl InvoiceErrorReason {
+    pub fn to_proto(self) -> model_pb_v3::invoice_error::Reason {
+        use model_pb_v3::invoice_error::Reason;
+
+        match self {
+            InvoiceErrorReason::AlreadyPaid => Reason::AlreadyPaid,
+            InvoiceErrorReason::WrongDestination => Reason::WrongDestination,
+            InvoiceErrorReason::SkuNotFound => Reason::SkuNotFound,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            InvoiceErrorReason::AlreadyPaid => "already_paid",
+            InvoiceErrorReason::WrongDestination => "wrong_destination",
+            InvoiceErrorReason::SkuNotFound => "sku_not_found",
+        }
+    }
+}
+
+/// An error for the invoice of a payment in a sign transaction request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvoiceError {
+    /// The index of the payment, and of its invoice in the invoice list.
+    pub op_index: u32,
+    pub reason: InvoiceErrorReason,
+}
+
+/// The response to a sign transaction webhook request.
+///
+/// Transactions are approved unless rejected, or unless an invoice error is added.
+#[derive(Debug, Clone, Default)]
+pub struct SignTransactionResponse {
+    rejected: bool,
+    invoice_errors: Vec<InvoiceError>,
+}
+
+impl SignTransactionResponse {
+    /// Returns a response that approves the transaction.
+    pub fn approve() -> SignTransactionResponse {
+        SignTransactionResponse::default()
+    }
+
+    /// Rejects the transaction.
+    pub fn reject(&mut self) {
+        self.rejected = true;
+    }
+
+    /// Rejects the transaction because the invoice of a payment has already been paid.
+    pub fn mark_already_paid(&mut self, op_index: u32) {
+        self.add_invoice_error(op_index, InvoiceErrorReason::AlreadyPaid);
+    }
+
+    /// Rejects the transaction because a payment is to the wrong destination.
+    pub fn mark_wrong_destination(&mut self, op_index: u32) {
+        self.add_invoice_error(op_index, InvoiceErrorReason::WrongDestination);
+    }
+
+    /// Rejects the transaction because the invoice of a payment contains an unknown SKU.
+    pub fn mark_sku_not_found(&mut self, op_index: u32) {
+        self.add_invoice_error(op_index, InvoiceErrorReason::SkuNotFound);
+    }
+
+    /// Rejects the transaction because of the invoice of a payment.
+    pub fn add_invoice_error(&mut self, op_index: u32, reason: InvoiceErrorReason) {
+        self.rejected = true;
+        self.invoice_errors.push(InvoiceError { op_index, reason });
+    }
+
+    pub fn is_rejected(&self) -> bool {
+        self.rejected
+    }
+
+    pub fn invoice_errors(&self) -> &[InvoiceError] {
+        &self.invoice_errors
+    }
+
+    /// Returns the HTTP status code of the response.
+    pub fn status_code(&self) -> u16 {
+        if self.rejected {
+            403
+        } else {
+            200
+        }
+    }
+
+    /// Returns the JSON body of the response.
+    pub fn to_json(&self) -> Vec<u8> {
+        let invoice_errors = self
+            .invoice_errors
+            .iter()
+            .map(|e| InvoiceErrorJson {
+                operation_index: e.op_index,
+                reason: e.reason.as_str(),
+            })
+            .collect();
+
+        serde_json::to_vec(&SignTransactionResponseJson { invoice_errors }).unwrap()
+    }
+}
+
+#[derive(Deserialize)]
+struct SignTransactionRequestJson {
+    #[serde(default)]
+    kin_version: u32,
+    solana_transaction: String,
+    invoice_list: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SignTransactionResponseJson {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    invoice_errors: Vec<InvoiceErrorJson>,
+}
+
+#[derive(Serialize)]
+struct InvoiceErrorJson {
+    operation_index: u32,
+    reason: &'static str,
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{
+            key::private::PrivateKey,
+            model::{invoice::Invoice, memo::Memo, transaction_type::TransactionType},
+            solana::memo::program::{MemoParams, MemoProgram},
+        },
+        prost::Message,
+        serde_json::{json, Value},
+    };
+
+    fn spend_tx(foreign_key: &[u8], amounts: &[u64]) -> SolanaTransaction {
+        let owner = PrivateKey::rand().public_key().to_solana_key();
+
+        let memo = Memo::new(1, TransactionType::Spend, 1, foreign_key);
+        let mut instructions = vec![MemoProgram::memo(MemoParams::new(memo.to_base64()))];
+        for amount in amounts {
+            let dest = PrivateKey::rand().public_key().to_solana_key();
+            instructions.push(
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &owner,
+                    &dest,
+                    &owner,
+                    &[],
+                    *amount,
+                )
+                .unwrap(),
+            );
+        }
+
+        SolanaTransaction::new_with_payer(&instructions, Some(&owner))
+    }
+
+    #[test]
+    fn parse_request() {
+        let invoice_list = InvoiceList::new(&[
+            Invoice::new("first", 10, None, None),
+            Invoice::new("second", 20, None, None),
+        ]);
+        let tx = spend_tx(&invoice_list.get_sha244_hash(), &[10, 20]);
+
+        let body = json!({
+            "kin_version": 4,
+            "solana_transaction": base64::encode(bincode::serialize(&tx).unwrap()),
+            "invoice_list": base64::encode(invoice_list.to_proto().encode_to_vec()),
+        });
+        let req = SignTransactionRequest::from_json(body.to_string().as_bytes()).unwrap();
+
+        assert_eq!(req.kin_version, 4);
+        assert_eq!(req.tx_id(), Some(TransactionId::from(tx.signatures[0])));
+        assert_eq!(req.invoice_list.unwrap().invoices.len(), 2);
+        assert_eq!(req.payments.len(), 2);
+        for (payment, quarks) in req.payments.iter().zip([10, 20]) {
+            assert_eq!(payment.quarks, quarks);
+            assert_eq!(payment.tx_type, TransactionType::Spend);
+            assert_eq!(payment.invoice.as_ref().unwrap().items[0].amount, quarks);
+        }
+    }
+
+    #[test]
+    fn parse_request_with_mismatched_invoices() {
+        let invoice_list = InvoiceList::new(&[Invoice::new("first", 10, None, None)]);
+        let tx = spend_tx(&invoice_list.get_sha244_hash(), &[10, 20]);
+
+        let body = json!({
+            "solana_transaction": base64::encode(bincode::serialize(&tx).unwrap()),
+            "invoice_list": base64::encode(invoice_list.to_proto().encode_to_vec()),
+        });
+        assert!(matches!(
+            SignTransactionRequest::from_json(body.to_string().as_bytes()),
+            Err(Error::InvalidWebhookPayload(_))
+        ));
+    }
+
+    #[test]
+    fn parse_request_with_malformed_transaction() {
+        let parse = |tx: &SolanaTransaction| {
+            let body = json!({
+                "solana_transaction": base64::encode(bincode::serialize(tx).unwrap()),
+            });
+            SignTransactionRequest::from_json(body.to_string().as_bytes())
+        };
+
+        let tx = spend_tx(&[], &[10]);
+        let keys_len = tx.message.account_keys.len() as u8;
+
+        let mut bad_program = tx.clone();
+        bad_program.message.instructions[1].program_id_index = keys_len;
+        assert!(matches!(
+            parse(&bad_program),
+            Err(Error::InvalidWebhookPayload(_))
+        ));
+
+        let mut bad_account = tx.clone();
+        bad_account.message.instructions[1].accounts[0] = keys_len;
+        assert!(matches!(
+            parse(&bad_account),
+            Err(Error::InvalidWebhookPayload(_))
+        ));
+
+        let mut bad_memo = tx;
+        bad_memo.message.instructions[0].data = vec![0xff];
+        assert!(matches!(
+            parse(&bad_memo),
+            Err(Error::InvalidWebhookPayload(_))
+        ));
+    }
+
+    #[test]
+    fn approve() {
+        let resp = SignTransactionResponse::approve();
+
+        assert!(!resp.is_rejected());
+        assert_eq!(resp.status_code(), 200);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&resp.to_json()).unwrap(),
+            json!({})
+        );
+    }
+
+    #[test]
+    fn reject_with_invoice_errors() {
+        let mut resp = SignTransactionResponse::approve();
+        resp.reject();
+        assert_eq!(resp.status_code(), 403);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&resp.to_json()).unwrap(),
+            json!({})
+        );
+
+        let mut resp = SignTransactionResponse::approve();
+        resp.mark_already_paid(0);
+        resp.mark_wrong_destination(1);
+        resp.mark_sku_not_found(2);
+        assert!(resp.is_rejected());
+        assert_eq!(resp.status_code(), 403);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&resp.to_json()).unwrap(),
+            json!({
+                "invoice_errors": [
+                    {"operation_index": 0, "reason": "already_paid"},
+                    {"operation_index": 1, "reason": "wrong_destination"},
+                    {"operation_index": 2, "reason": "sku_not_found"},
+                ],
+            })
+        );
+
+        // The reasons are those the client recognises in Agora responses.
+        let errors: Vec<Error> = resp
+            .invoice_errors()
+            .iter()
+            .map(|e| {
+                Error::from_invoice_error(&model_pb_v3::InvoiceError {
+                    op_index: e.op_index,
+                    reason: e.reason.to_proto() as i32,
+                    ..Default::default()
+                })
+            })
+            .collect();
+        assert!(matches!(errors[0], Error::AlreadyPaid));
+        assert!(matches!(errors[1], Error::WrongDestination));
+        assert!(matches!(errors[2], Error::SkuNotFound));
+    }
+}
@@ -0,0 +1,87 @@
+use {
+    crate::error::Error,
+    hmac::{Hmac, Mac, NewMac},
+    sha2::Sha256,
+};
+
+/// The header Agora sends the signature of a webhook body in.
+pub const HMAC_HEADER: &str = "X-Agora-HMAC-SHA256";
+
+/// Verifies the signatures of webhook requests against the app's webhook secret.
+///
+/// Requests should be verified before their bodies are parsed.
+#[derive(Clone)]
+pub struct SignatureVerifier {
+    secret: Vec<u8>,
+}
+
+impl SignatureVerifier {
+    pub fn new(secret: &[u8]) -> SignatureVerifier {
+        SignatureVerifier {
+            secret: secret.to_vec(),
+        }
+    }
+
+    /// Verifies the base64-encoded signature of a request body, as sent in the
+    /// [`HMAC_HEADER`](HMAC_HEADER) header.
+    ///
+    /// Fails with `InvalidWebhookSignature` if the request is not signed, or if the signature does
+    /// not match the body. The comparison is in constant time.
+    pub fn verify(&self, signature: Option<&str>, body: &[u8]) -> Result<(), Error> {
+        let signature = signature
+            .and_then(|signature| base64::decode(signature.trim()).ok())
+            .ok_or(Error::InvalidWebhookSignature)?;
+
+        let mut mac = self.mac();
+        mac.update(body);
+        mac.verify(&signature)
+            .map_err(|_| Error::InvalidWebhookSignature)
+    }
+
+    /// Returns the base64-encoded signature of a request body.
+    pub fn sign(&self, body: &[u8]) -> String {
+        let mut mac = self.mac();
+        mac.update(body);
+        base64::encode(mac.finalize().into_bytes())
+    }
+
+    fn mac(&self) -> Hmac<Sha256> {
+        // HMAC accepts keys of any length.
+        Hmac::new_from_slice(&self.secret).unwrap()
+    }
+}
+
+impl std::fmt::Debug for SignatureVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SignatureVerifier").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify() {
+        let verifier = SignatureVerifier::new(b"secret");
+        let body = br#"{"kin_version":4}"#;
+
+        // Generated with: echo -n '{"kin_version":4}' | openssl dgst -sha256 -hmac secret -binary | base64
+        let signature = verifier.sign(body);
+        assert_eq!(signature, "fKsa4bMXe0f2fcqEQ3sfuU4CGTkmJkFHmm4xDkp4rpw=");
+        assert!(verifier.verify(Some(&signature), body).is_ok());
+
+        let other = SignatureVerifier::new(b"other");
+        for (verifier, signature, body) in [
+            (&verifier, None, &body[..]),
+            (&verifier, Some("not base64"), &body[..]),
+            (&verifier, Some(signature.as_str()), br#"{"kin_version":3}"#),
+            (&other, Some(signature.as_str()), &body[..]),
+        ] {
+            assert!(matches!(
+                verifier.verify(signature, body),
+                Err(Error::InvalidWebhookSignature)
+            ));
+        }
+    }
+}